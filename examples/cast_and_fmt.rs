@@ -163,7 +163,7 @@ Cancel	0x0f	0xff	0x1000	0	0	0	0x108"#;
     let formatted_control_map = r#"
 // Lockpicking
 RotatePick	0xff	0xa	0x000b	0	0	0	0x8
-RotateLock	0x1e+0xff	0xff	0x000c	0	0	0	0x8
+RotateLock	0x1e,0xff	0xff	0x000c	0	0	0	0x8
 DebugMode	0x35	0xff	0x4000	0	0	0	0x8
 Cancel	0x0f	0xff	0x1000	0	0	0	0x8
 
@@ -1,10 +1,8 @@
 use anyhow::Result;
 use controlmap_parser::{
-    parser::{EventLine, KeyID, Line},
-    scan_code::{GamepadCode, KeyboardCode, MouseCode},
+    parser::{EventLine, Line},
     ControlMap,
 };
-use core::str::FromStr;
 use tracing::trace;
 
 fn main() -> Result<()> {
@@ -55,6 +53,7 @@ Cancel	0x0f	0xff	0x1000	0	0	0	0x108"#;
             Line::Comment(comment) => trace!("{}", comment),
             Line::EventLine(event) => parse_event_line(event),
             Line::BlankLine => trace!("\n"),
+            Line::Invalid { raw, .. } => trace!("Invalid: {}", raw),
         }
     }
 
@@ -63,59 +62,8 @@ Cancel	0x0f	0xff	0x1000	0	0	0	0x108"#;
 
 fn parse_event_line(event: EventLine) {
     trace!("Event Name: {}", event.event_name);
-    parse_keyboard(event.keyboard_id);
-    parse_game(event.gamepad_id);
-    parse_mouse(event.mouse_id);
+    trace!("Keyboard: {} => {:?}", event.keyboard_id, event.keyboard());
+    trace!("Game: {} => {:?}", event.gamepad_id, event.gamepad());
+    trace!("Mouse: {} => {:?}", event.mouse_id, event.mouse());
     trace!("\n");
 }
-
-fn parse_game(key_id: KeyID) {
-    match key_id {
-        KeyID::Or(vec) => {
-            for key in vec {
-                parse_game(key)
-            }
-        }
-        KeyID::And(vec) => {
-            for key in vec {
-                parse_game(key)
-            }
-        }
-        KeyID::One(key) => trace!("Game: {} => {:?}", &key, GamepadCode::from_str(&key)),
-        KeyID::Alias(alias) => trace!("GameAlias: {}", alias),
-    };
-}
-
-fn parse_mouse(key_id: KeyID) {
-    match key_id {
-        KeyID::Or(vec) => {
-            for key in vec {
-                parse_mouse(key)
-            }
-        }
-        KeyID::And(vec) => {
-            for key in vec {
-                parse_mouse(key)
-            }
-        }
-        KeyID::One(key) => trace!("Mouse: {} => {:?}", &key, MouseCode::from_str(&key)),
-        KeyID::Alias(alias) => trace!("MouseAlias: {}", alias),
-    };
-}
-
-fn parse_keyboard(key_id: KeyID) {
-    match key_id {
-        KeyID::Or(vec) => {
-            for key in vec {
-                parse_keyboard(key)
-            }
-        }
-        KeyID::And(vec) => {
-            for key in vec {
-                parse_keyboard(key)
-            }
-        }
-        KeyID::One(key) => trace!("Keyboard: {} => {:?}", &key, KeyboardCode::from_str(&key)),
-        KeyID::Alias(alias) => trace!("KeyAlias: {}", alias),
-    };
-}
@@ -0,0 +1,146 @@
+//! Alias resolution.
+//!
+//! A key id such as `!0,Activate` means "whatever `Activate` is bound to". The game looks the
+//! name up among the events of context 0 (Main Gameplay), on the same device, and an alias may
+//! point to another alias.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{context::ContextKind, scan_code::Device, ControlMap};
+//!
+//! let control_map = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+//! let accept = control_map.effective_binding(ContextKind::MenuMode, "Accept", Device::Keyboard)?;
+//! assert_eq!(accept.key_id.to_string(), "0x12");
+//! assert_eq!(accept.chain, ["Activate"]);
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::{ControlMap, ControlMapError, Result};
+use crate::parser::KeyID;
+use crate::scan_code::Device;
+
+/// A key id with every alias replaced by the binding it points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResolvedBinding {
+    pub key_id: KeyID,
+    /// Names of the Main Gameplay events followed, in order. Empty if there was no alias.
+    pub chain: Vec<String>,
+}
+
+impl ControlMap {
+    /// Binding of `device` that actually triggers the event named `name` in `context`.
+    ///
+    /// # Errors
+    /// - The context or event does not exist.
+    /// - An alias names no Main Gameplay event, or aliases refer to each other in a cycle.
+    pub fn effective_binding(
+        &self,
+        context: ContextKind,
+        name: &str,
+        device: Device,
+    ) -> Result<ResolvedBinding> {
+        let event = self
+            .context(context)
+            .ok_or(ControlMapError::ContextNotFound(context))?
+            .event(name)
+            .ok_or_else(|| ControlMapError::EventNotFound {
+                context,
+                event: name.into(),
+            })?;
+        self.resolve_alias(event.key_id(device), device)
+    }
+
+    /// Replace every alias in `key_id` with the `device` binding of the Main Gameplay event it
+    /// names, following aliases of aliases.
+    ///
+    /// # Errors
+    /// An alias names no Main Gameplay event, or aliases refer to each other in a cycle.
+    pub fn resolve_alias(&self, key_id: &KeyID, device: Device) -> Result<ResolvedBinding> {
+        let mut chain = Vec::new();
+        let key_id = self.resolve(key_id, device, &mut Vec::new(), &mut chain)?;
+        Ok(ResolvedBinding { key_id, chain })
+    }
+
+    /// `path` holds the aliases being followed to reach `key_id`, `chain` every alias followed.
+    fn resolve(
+        &self,
+        key_id: &KeyID,
+        device: Device,
+        path: &mut Vec<String>,
+        chain: &mut Vec<String>,
+    ) -> Result<KeyID> {
+        let mut resolve_all = |keys: &[KeyID]| {
+            keys.iter()
+                .map(|key| self.resolve(key, device, path, chain))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        match key_id {
+            KeyID::Or(keys) => Ok(KeyID::Or(resolve_all(keys)?)),
+            KeyID::And(keys) => Ok(KeyID::And(resolve_all(keys)?)),
+            KeyID::One(_) => Ok(key_id.clone()),
+            KeyID::Alias(alias) => {
+                if path.contains(alias) {
+                    let mut cycle = path.clone();
+                    cycle.push(alias.clone());
+                    return Err(ControlMapError::AliasCycle { chain: cycle });
+                }
+                let target = self
+                    .context(ContextKind::MainGameplay)
+                    .and_then(|main| main.event(alias))
+                    .ok_or_else(|| ControlMapError::DanglingAlias {
+                        alias: alias.clone(),
+                        chain: chain.clone(),
+                    })?;
+
+                path.push(alias.clone());
+                chain.push(alias.clone());
+                let resolved = self.resolve(target.key_id(device), device, path, chain);
+                path.pop();
+                resolved
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_alias_chain() {
+        let control_map = ControlMap::from_txt(
+            "// Main Gameplay\nA\t!0,B\t0xff\t0xff\t0\t0\t0\nB\t0x12\t0xff\t0xff\t0\t0\t0\n\n// Menu Mode\nAccept\t!0,A\t0xff\t0xff\t0\t0\t0\n",
+        )
+        .unwrap();
+        let accept = control_map
+            .effective_binding(ContextKind::MenuMode, "Accept", Device::Keyboard)
+            .unwrap();
+        assert_eq!(accept.key_id, KeyID::One("0x12".parse().unwrap()));
+        assert_eq!(accept.chain, ["A", "B"]);
+    }
+
+    #[test]
+    fn test_alias_errors() {
+        let control_map = ControlMap::from_txt(
+            "// Main Gameplay\nA\t!0,B\t!0,Missing\t0xff\t0\t0\t0\nB\t!0,A\t0xff\t0xff\t0\t0\t0\n",
+        )
+        .unwrap();
+        assert_eq!(
+            control_map.effective_binding(ContextKind::MainGameplay, "A", Device::Keyboard),
+            Err(ControlMapError::AliasCycle {
+                chain: vec!["B".into(), "A".into(), "B".into()]
+            })
+        );
+        assert_eq!(
+            control_map.effective_binding(ContextKind::MainGameplay, "A", Device::Mouse),
+            Err(ControlMapError::DanglingAlias {
+                alias: "Missing".into(),
+                chain: vec![]
+            })
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! [`proptest`] generators.
+//!
+//! Only values the parser can produce are generated, so every one of them satisfies
+//! `ControlMap::from_txt(&x.to_string()) == x`: `Or` holds `And`s and single codes, `And` holds
+//! single codes, and names never contain tabs or line breaks. Invalid lines are not generated.
+use crate::controlmap::ControlMap;
+use crate::parser::{EventLine, KeyCode, KeyID, Line};
+use proptest::prelude::*;
+
+/// Event or alias name: no tabs or line breaks, and not mistaken for a comment or blank line.
+fn name() -> impl Strategy<Value = String> {
+    "[A-Za-z!][^\t\r\n]{0,20}"
+}
+
+/// `0x` followed by hex digits, possibly zero-padded or uppercase.
+fn hex() -> impl Strategy<Value = String> {
+    (any::<u32>(), 0..3_usize, any::<bool>()).prop_map(|(code, zeros, upper)| {
+        let digits = format!("{}{:x}", "0".repeat(zeros), code);
+        match upper {
+            true => format!("0x{}", digits.to_uppercase()),
+            false => format!("0x{}", digits),
+        }
+    })
+}
+
+impl Arbitrary for KeyCode {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        hex()
+            .prop_map(|hex| hex.parse().expect("generated valid hex"))
+            .boxed()
+    }
+}
+
+impl Arbitrary for KeyID {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let one = any::<KeyCode>().prop_map(KeyID::One);
+        let and = prop::collection::vec(one.clone(), 2..4).prop_map(KeyID::And);
+        let or =
+            prop::collection::vec(prop_oneof![one.clone(), and.clone()], 2..4).prop_map(KeyID::Or);
+        let alias = name().prop_map(KeyID::Alias);
+        prop_oneof![4 => one, 1 => and, 1 => or, 1 => alias].boxed()
+    }
+}
+
+impl Arbitrary for EventLine {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            name(),
+            any::<[KeyID; 3]>(),
+            any::<[bool; 3]>(),
+            prop::option::of(hex()),
+        )
+            .prop_map(
+                |(event_name, [keyboard_id, mouse_id, gamepad_id], remap, event_binary_flag)| {
+                    EventLine {
+                        event_name,
+                        keyboard_id,
+                        mouse_id,
+                        gamepad_id,
+                        remap_key: remap[0],
+                        remap_mouse: remap[1],
+                        remap_gamepad: remap[2],
+                        event_binary_flag,
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for Line {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            "[^\r\n]{0,30}".prop_map(Line::Comment),
+            any::<EventLine>().prop_map(Line::EventLine),
+            Just(Line::BlankLine),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for ControlMap {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop::collection::vec(any::<Line>(), 0..40)
+            .prop_map(ControlMap::from_lines)
+            .boxed()
+    }
+}
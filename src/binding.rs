@@ -0,0 +1,134 @@
+//! Typed key bindings.
+//!
+//! [`Binding`] mirrors [`KeyID`] with codes decoded into one of the [`scan_code`](crate::scan_code)
+//! enums, so a binding can be matched on `KeyboardCode::LShift` instead of `"0x2a"`.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{binding::Binding, parser::Line, scan_code::KeyboardCode, ControlMap};
+//!
+//! let control_map = ControlMap::from_txt("RotateLock\t0x1e,0xff\t0xff\t0x000c\t0\t0\t0\n")?;
+//! let Some(Line::EventLine(event)) = control_map.iter().next() else {
+//!     unreachable!()
+//! };
+//!
+//! assert_eq!(
+//!     event.keyboard(),
+//!     Binding::Or(vec![Binding::One(KeyboardCode::A), Binding::Unmapped])
+//! );
+//! assert_eq!(event.mouse(), Binding::Unmapped);
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::parser::{EventLine, KeyCode, KeyID};
+use crate::scan_code::{GamepadCode, KeyboardCode, MouseCode, UNMAPPED};
+use num_traits::ToPrimitive;
+
+/// [`KeyID`] with its codes decoded as `T`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Binding<T> {
+    /// A known code.
+    One(T),
+    /// A code `T` does not know, kept as written.
+    Raw(KeyCode),
+    /// Any of the bindings, e.g. `0x1e,0xff`
+    Or(Vec<Binding<T>>),
+    /// All of the bindings at once, e.g. `0x0200+0x0040`
+    And(Vec<Binding<T>>),
+    /// Same binding as the named event of the main gameplay context, e.g. `!0,Sprint`
+    Alias(String),
+    /// `0xff`
+    Unmapped,
+}
+
+impl<T: TryFrom<usize>> Binding<T> {
+    /// Decode every code of `key_id`. Codes `T` does not know become [`Binding::Raw`].
+    pub fn from_key_id(key_id: &KeyID) -> Self {
+        match key_id {
+            KeyID::Or(keys) => Self::Or(keys.iter().map(Self::from_key_id).collect()),
+            KeyID::And(keys) => Self::And(keys.iter().map(Self::from_key_id).collect()),
+            KeyID::One(key) if key.code() == UNMAPPED => Self::Unmapped,
+            KeyID::One(key) => match T::try_from(key.code() as usize) {
+                Ok(code) => Self::One(code),
+                Err(_) => Self::Raw(key.clone()),
+            },
+            KeyID::Alias(alias) => Self::Alias(alias.clone()),
+        }
+    }
+}
+
+impl<T: ToPrimitive> Binding<T> {
+    /// Encode back into a [`KeyID`]. Known codes are written in canonical form, raw codes as
+    /// they were read.
+    pub fn to_key_id(&self) -> KeyID {
+        match self {
+            Self::One(code) => {
+                let code = code.to_u32().unwrap_or(UNMAPPED);
+                KeyID::One(KeyCode::new(code))
+            }
+            Self::Raw(key) => KeyID::One(key.clone()),
+            Self::Or(bindings) => KeyID::Or(bindings.iter().map(Self::to_key_id).collect()),
+            Self::And(bindings) => KeyID::And(bindings.iter().map(Self::to_key_id).collect()),
+            Self::Alias(alias) => KeyID::Alias(alias.clone()),
+            Self::Unmapped => KeyID::One(KeyCode::new(UNMAPPED)),
+        }
+    }
+}
+
+impl<T> Binding<T> {
+    pub fn is_unmapped(&self) -> bool {
+        matches!(self, Self::Unmapped)
+    }
+}
+
+impl<T: TryFrom<usize>> From<&KeyID> for Binding<T> {
+    fn from(key_id: &KeyID) -> Self {
+        Self::from_key_id(key_id)
+    }
+}
+
+impl EventLine {
+    /// Keyboard binding decoded as [`KeyboardCode`]s.
+    pub fn keyboard(&self) -> Binding<KeyboardCode> {
+        Binding::from_key_id(&self.keyboard_id)
+    }
+
+    /// Mouse binding decoded as [`MouseCode`]s.
+    pub fn mouse(&self) -> Binding<MouseCode> {
+        Binding::from_key_id(&self.mouse_id)
+    }
+
+    /// Gamepad binding decoded as [`GamepadCode`]s.
+    pub fn gamepad(&self) -> Binding<GamepadCode> {
+        Binding::from_key_id(&self.gamepad_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_unknown_codes_are_kept() {
+        let key_id = KeyID::And(vec![
+            KeyID::One("0x0200".parse().unwrap()),
+            KeyID::One("0x0040".parse().unwrap()),
+            KeyID::One("0x0003".parse().unwrap()),
+        ]);
+        let binding = Binding::<GamepadCode>::from_key_id(&key_id);
+        assert_eq!(
+            binding,
+            Binding::And(vec![
+                Binding::One(GamepadCode::_360_RB),
+                Binding::One(GamepadCode::_360_L3),
+                Binding::Raw("0x0003".parse().unwrap()),
+            ])
+        );
+
+        let encoded = binding.to_key_id();
+        assert_eq!(encoded, key_id);
+        assert_eq!(encoded.to_string(), "0x200+0x40+0x0003");
+    }
+}
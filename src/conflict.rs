@@ -0,0 +1,311 @@
+//! Binding conflicts.
+//!
+//! Only one input context is active at a time, so keys are compared within each context and
+//! device. Aliases are resolved first, which is how a binding in Main Gameplay can collide with
+//! one in another context.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{
+//!     conflict::{AllowList, ConflictKind},
+//!     context::ContextKind,
+//!     parser::KeyID,
+//!     scan_code::Device,
+//!     ControlMap,
+//! };
+//!
+//! let vanilla = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+//! let allowed = AllowList::from_baseline(&vanilla);
+//! assert!(vanilla.conflicts(&allowed).is_empty());
+//!
+//! // A mod binds Quick Inventory to Tab, already used by Tween Menu.
+//! let mut modded = vanilla.clone();
+//! modded.set_binding(
+//!     ContextKind::MainGameplay,
+//!     "Quick Inventory",
+//!     Device::Keyboard,
+//!     KeyID::One("0x0f".parse()?),
+//! )?;
+//! let conflicts = modded.conflicts(&allowed);
+//! assert_eq!(conflicts.len(), 1);
+//! assert_eq!(conflicts[0].kind, ConflictKind::SameKey);
+//! assert_eq!(conflicts[0].events, ["Tween Menu", "Quick Inventory"]);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::ControlMap;
+use crate::parser::{KeyCode, KeyID};
+use crate::scan_code::{Device, UNMAPPED};
+use core::fmt;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConflictKind {
+    /// Several events are triggered by the same key or chord.
+    SameKey,
+    /// The chord of the first event contains the whole binding of the second, so pressing the
+    /// chord triggers both.
+    ChordShadow,
+    /// Like [`ConflictKind::SameKey`], but at least one of the events gets the key through a
+    /// `!0,` alias.
+    AliasDuplicate,
+}
+
+/// Events of one context sharing a key on one device.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub context: ContextKind,
+    pub device: Device,
+    /// The shared key, or chord for [`ConflictKind::ChordShadow`].
+    pub key: KeyID,
+    /// Event names in file order.
+    pub events: Vec<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let events = self.events.join("`, `");
+        match self.kind {
+            ConflictKind::SameKey => write!(
+                f,
+                "{} ({}): {} triggers `{}`",
+                self.context, self.device, self.key, events
+            ),
+            ConflictKind::AliasDuplicate => write!(
+                f,
+                "{} ({}): {} triggers `{}` (through an alias)",
+                self.context, self.device, self.key, events
+            ),
+            ConflictKind::ChordShadow => write!(
+                f,
+                "{} ({}): chord {} of `{}` also triggers `{}`",
+                self.context,
+                self.device,
+                self.key,
+                self.events[0],
+                self.events[1..].join("`, `")
+            ),
+        }
+    }
+}
+
+/// Conflicts that are known to be intended, e.g. the overlaps of the vanilla file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllowList {
+    allowed: HashSet<Conflict>,
+}
+
+impl AllowList {
+    /// Allow every conflict found in `baseline`.
+    pub fn from_baseline(baseline: &ControlMap) -> Self {
+        Self {
+            allowed: baseline.conflicts(&Self::default()).into_iter().collect(),
+        }
+    }
+
+    pub fn allow(&mut self, conflict: Conflict) {
+        self.allowed.insert(conflict);
+    }
+
+    pub fn is_allowed(&self, conflict: &Conflict) -> bool {
+        self.allowed.contains(conflict)
+    }
+}
+
+/// One way to trigger an event: a key, or several keys pressed together.
+struct Trigger<'a> {
+    event: &'a str,
+    keys: Vec<KeyCode>,
+    via_alias: bool,
+}
+
+impl ControlMap {
+    /// Find events that share keys, skipping the conflicts in `allowed`.
+    ///
+    /// Aliases that cannot be resolved are ignored here; see [`ControlMap::resolve_alias`].
+    pub fn conflicts(&self, allowed: &AllowList) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for context in self.contexts() {
+            for device in Device::ALL {
+                let mut triggers = Vec::new();
+                for event in &context.events {
+                    let Ok(resolved) = self.resolve_alias(event.key_id(device), device) else {
+                        continue;
+                    };
+                    let via_alias = !resolved.chain.is_empty();
+                    for keys in key_sets(&resolved.key_id) {
+                        triggers.push(Trigger {
+                            event: &event.event_name,
+                            keys,
+                            via_alias,
+                        });
+                    }
+                }
+                conflicts.extend(find_conflicts(context.kind, device, &triggers));
+            }
+        }
+
+        conflicts.retain(|conflict| !allowed.is_allowed(conflict));
+        conflicts
+    }
+}
+
+fn find_conflicts(context: ContextKind, device: Device, triggers: &[Trigger]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut seen: HashSet<&[KeyCode]> = HashSet::new();
+
+    for trigger in triggers {
+        if !seen.insert(&trigger.keys) {
+            continue;
+        }
+        let same: Vec<_> = triggers
+            .iter()
+            .filter(|other| other.keys == trigger.keys)
+            .collect();
+        let events = event_names(same.iter().map(|other| other.event));
+        if events.len() > 1 {
+            let kind = match same.iter().any(|other| other.via_alias) {
+                true => ConflictKind::AliasDuplicate,
+                false => ConflictKind::SameKey,
+            };
+            conflicts.push(Conflict {
+                kind,
+                context,
+                device,
+                key: key_id(&trigger.keys),
+                events,
+            });
+        }
+    }
+
+    // Identical chords are already a `SameKey`, so each chord is reported for its first event.
+    let mut reported: HashSet<&[KeyCode]> = HashSet::new();
+    for chord in triggers.iter().filter(|trigger| trigger.keys.len() > 1) {
+        if !reported.insert(&chord.keys) {
+            continue;
+        }
+        let shadowed = triggers.iter().filter(|other| {
+            other.event != chord.event
+                && other.keys.len() < chord.keys.len()
+                && other.keys.iter().all(|key| chord.keys.contains(key))
+        });
+        let events =
+            event_names(core::iter::once(chord.event).chain(shadowed.map(|other| other.event)));
+        if events.len() > 1 {
+            conflicts.push(Conflict {
+                kind: ConflictKind::ChordShadow,
+                context,
+                device,
+                key: key_id(&chord.keys),
+                events,
+            });
+        }
+    }
+    conflicts
+}
+
+/// Distinct names, keeping the first occurrence.
+fn event_names<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut events: Vec<String> = Vec::new();
+    for name in names {
+        if !events.iter().any(|event| event == name) {
+            events.push(name.into());
+        }
+    }
+    events
+}
+
+fn key_id(keys: &[KeyCode]) -> KeyID {
+    match keys {
+        [key] => KeyID::One(key.clone()),
+        keys => KeyID::And(keys.iter().cloned().map(KeyID::One).collect()),
+    }
+}
+
+/// Every set of keys that triggers `key_id`, each sorted and without unmapped keys.
+fn key_sets(key_id: &KeyID) -> Vec<Vec<KeyCode>> {
+    match key_id {
+        KeyID::Or(keys) => keys.iter().flat_map(key_sets).collect(),
+        KeyID::And(keys) => keys.iter().fold(vec![vec![]], |sets, key| {
+            let alternatives = match key_sets(key) {
+                alternatives if alternatives.is_empty() => vec![vec![]],
+                alternatives => alternatives,
+            };
+            sets.iter()
+                .flat_map(|set| {
+                    alternatives.iter().map(move |alternative| {
+                        let mut keys = set.clone();
+                        keys.extend(alternative.iter().cloned());
+                        keys.sort();
+                        keys.dedup();
+                        keys
+                    })
+                })
+                .collect()
+        }),
+        KeyID::One(key) if key.code() == UNMAPPED => vec![],
+        KeyID::One(key) => vec![vec![key.clone()]],
+        KeyID::Alias(_) => vec![],
+    }
+    .into_iter()
+    .filter(|keys| !keys.is_empty())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn key(hex: &str) -> KeyID {
+        KeyID::One(hex.parse().unwrap())
+    }
+
+    #[test]
+    fn test_conflict_kinds() {
+        let control_map = ControlMap::from_txt(
+            "// Main Gameplay\nActivate\t0x12\t0xff\t0x4000\t1\t1\t1\n\n// Menu Mode\nAccept\t!0,Activate\t0xff\t0x0200+0x4000\t0\t0\t0\nCancel\t0x12\t0xff\t0x4000\t0\t0\t0\nDone\t0xff\t0x1\t0x4000,0x0200+0x4000\t0\t0\t0\n",
+        )
+        .unwrap();
+        let conflicts = control_map.conflicts(&AllowList::default());
+
+        let chord = KeyID::And(vec![key("0x0200"), key("0x4000")]);
+        assert_eq!(
+            conflicts,
+            [
+                Conflict {
+                    kind: ConflictKind::AliasDuplicate,
+                    context: ContextKind::MenuMode,
+                    device: Device::Keyboard,
+                    key: key("0x12"),
+                    events: vec!["Accept".into(), "Cancel".into()],
+                },
+                Conflict {
+                    kind: ConflictKind::SameKey,
+                    context: ContextKind::MenuMode,
+                    device: Device::Gamepad,
+                    key: chord.clone(),
+                    events: vec!["Accept".into(), "Done".into()],
+                },
+                Conflict {
+                    kind: ConflictKind::SameKey,
+                    context: ContextKind::MenuMode,
+                    device: Device::Gamepad,
+                    key: key("0x4000"),
+                    events: vec!["Cancel".into(), "Done".into()],
+                },
+                Conflict {
+                    kind: ConflictKind::ChordShadow,
+                    context: ContextKind::MenuMode,
+                    device: Device::Gamepad,
+                    key: chord,
+                    events: vec!["Accept".into(), "Cancel".into(), "Done".into()],
+                },
+            ]
+        );
+    }
+}
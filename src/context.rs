@@ -0,0 +1,207 @@
+//! Input contexts.
+//!
+//! Blank lines in `controlmap.txt` start a new input context, and the game identifies each
+//! context only by its position. [`ControlMap::contexts`] groups the lines accordingly.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{context::ContextKind, ControlMap};
+//!
+//! let control_map = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+//! let contexts = control_map.contexts();
+//! assert_eq!(contexts.len(), 17);
+//! assert_eq!(contexts[0].kind, ContextKind::MainGameplay);
+//! assert_eq!(contexts[0].header, Some("Main Gameplay"));
+//!
+//! let favor = control_map.context(ContextKind::Favor).unwrap();
+//! assert_eq!(favor.header, Some("Favor"));
+//! assert_eq!(favor.events[0].event_name, "Cancel");
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::controlmap::ControlMap;
+use crate::parser::{EventLine, Line};
+use core::fmt;
+use core::ops::Range;
+
+/// Input context, named after its position in the vanilla Skyrim SE file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ContextKind {
+    MainGameplay,
+    MenuMode,
+    Console,
+    ItemMenus,
+    Inventory,
+    DebugText,
+    FavoritesMenu,
+    MapMenu,
+    Stats,
+    Cursor,
+    Book,
+    DebugOverlay,
+    Journal,
+    TFCMode,
+    /// Debug Map Menu-like mode (but not the actual map menu)
+    DebugMapMenu,
+    Lockpicking,
+    Favor,
+    /// A section past the known contexts, by its 0-based position.
+    Other(usize),
+}
+
+impl ContextKind {
+    /// Known contexts in file order.
+    pub const ALL: [Self; 17] = [
+        Self::MainGameplay,
+        Self::MenuMode,
+        Self::Console,
+        Self::ItemMenus,
+        Self::Inventory,
+        Self::DebugText,
+        Self::FavoritesMenu,
+        Self::MapMenu,
+        Self::Stats,
+        Self::Cursor,
+        Self::Book,
+        Self::DebugOverlay,
+        Self::Journal,
+        Self::TFCMode,
+        Self::DebugMapMenu,
+        Self::Lockpicking,
+        Self::Favor,
+    ];
+
+    /// Context of the section at 0-based `index`.
+    pub fn from_index(index: usize) -> Self {
+        Self::ALL.get(index).copied().unwrap_or(Self::Other(index))
+    }
+
+    /// 0-based position in the file.
+    pub fn index(&self) -> usize {
+        match self {
+            Self::Other(index) => *index,
+            known => Self::ALL
+                .iter()
+                .position(|kind| kind == known)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Header comment used by the vanilla file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MainGameplay => "Main Gameplay",
+            Self::MenuMode => "Menu Mode",
+            Self::Console => "Console",
+            Self::ItemMenus => "Item Menus",
+            Self::Inventory => "Inventory",
+            Self::DebugText => "Debug Text",
+            Self::FavoritesMenu => "Favorites menu",
+            Self::MapMenu => "Map Menu",
+            Self::Stats => "Stats",
+            Self::Cursor => "Cursor",
+            Self::Book => "Book",
+            Self::DebugOverlay => "Debug overlay",
+            Self::Journal => "Journal",
+            Self::TFCMode => "TFC mode",
+            Self::DebugMapMenu => "Debug Map Menu-like mode (but not the actual map menu)",
+            Self::Lockpicking => "Lockpicking",
+            Self::Favor => "Favor",
+            Self::Other(_) => "Unknown",
+        }
+    }
+
+    /// Guess the context from a header comment such as `// Main Gameplay`.
+    pub fn from_header(header: &str) -> Option<Self> {
+        let header = header.trim().trim_start_matches("//").trim();
+        Self::ALL.into_iter().find(|kind| match kind {
+            Self::DebugMapMenu => header.to_lowercase().starts_with("debug map menu"),
+            kind => header.eq_ignore_ascii_case(kind.name()),
+        })
+    }
+}
+
+impl fmt::Display for ContextKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Other(index) => write!(f, "Unknown context #{}", index),
+            known => write!(f, "{}", known.name()),
+        }
+    }
+}
+
+/// Lines of one input context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputContext<'a> {
+    pub kind: ContextKind,
+    /// Trimmed comment right above the first event, e.g. `Main Gameplay`.
+    pub header: Option<&'a str>,
+    pub events: Vec<&'a EventLine>,
+    /// Indices of [`ControlMap::iter`] covered by this context, without the blank line
+    /// separating it from the next one.
+    pub lines: Range<usize>,
+}
+
+impl<'a> InputContext<'a> {
+    /// Find an event of this context by name.
+    pub fn event(&self, name: &str) -> Option<&'a EventLine> {
+        self.events
+            .iter()
+            .copied()
+            .find(|event| event.event_name == name)
+    }
+}
+
+impl ControlMap {
+    /// Group lines into input contexts. Every blank line starts a new context.
+    pub fn contexts(&self) -> Vec<InputContext<'_>> {
+        let mut contexts = Vec::new();
+        let mut start = 0;
+        let lines: Vec<&Line> = self.iter().collect();
+
+        for (idx, line) in lines.iter().enumerate() {
+            if matches!(line, Line::BlankLine) {
+                contexts.push(new_context(&lines, contexts.len(), start..idx));
+                start = idx + 1;
+            }
+        }
+        contexts.push(new_context(&lines, contexts.len(), start..lines.len()));
+
+        contexts
+    }
+
+    /// Find a context by kind.
+    pub fn context(&self, kind: ContextKind) -> Option<InputContext<'_>> {
+        self.contexts().into_iter().nth(kind.index())
+    }
+}
+
+fn new_context<'a>(lines: &[&'a Line], index: usize, range: Range<usize>) -> InputContext<'a> {
+    let section = &lines[range.clone()];
+    let first_event = section
+        .iter()
+        .position(|line| matches!(line, Line::EventLine(_)))
+        .unwrap_or(section.len());
+    let header = section[..first_event]
+        .iter()
+        .rev()
+        .find_map(|line| match line {
+            Line::Comment(comment) => Some(comment.trim()),
+            _ => None,
+        });
+    let events = section
+        .iter()
+        .filter_map(|line| match line {
+            Line::EventLine(event) => Some(event),
+            _ => None,
+        })
+        .collect();
+
+    InputContext {
+        kind: ContextKind::from_index(index),
+        header,
+        events,
+        lines: range,
+    }
+}
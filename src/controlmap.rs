@@ -1,15 +1,17 @@
-use crate::parser::{control_map_parser, Line};
-use crate::scan_code::ScanCodeError;
+use crate::context::ContextKind;
+use crate::diagnostic::Diagnostic;
+use crate::names::NormalizedKeys;
+use crate::parser::{control_map_parser_tolerant, EventLine, KeyCode, Line};
+use crate::scan_code::{Device, ScanCodeError};
 use core::fmt;
 use core::slice::Iter;
-use nom::error::convert_error;
 use std::vec::IntoIter;
 
 /// Structure with an array containing `controlmap.txt` line by line.
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ControlMap {
-    lines: Vec<Line>,
+    pub(crate) lines: Vec<Line>,
 }
 
 impl fmt::Display for ControlMap {
@@ -25,29 +27,89 @@ pub type Result<T, E = ControlMapError> = core::result::Result<T, E>;
 
 impl ControlMap {
     /// ControlMap from text input
+    ///
+    /// # Errors
+    /// The first line that could not be parsed is reported as a [`Diagnostic`].
     pub fn from_txt(txt: &str) -> Result<Self> {
-        let (remain, lines) = control_map_parser(txt).map_err(|err| {
-            let err = match err {
-                nom::Err::Incomplete(_) => "Incomplete error".into(),
-                nom::Err::Error(err) => convert_error(txt, err),
-                nom::Err::Failure(err) => convert_error(txt, err),
-            };
-
-            ControlMapError::ParseError(err)
-        })?;
+        let (control_map, diagnostics) = Self::from_txt_tolerant(txt);
 
-        match remain.is_empty() {
-            true => Ok(Self { lines }),
-            false => Err(ControlMapError::Incomplete(remain.into())),
+        match diagnostics.into_iter().next() {
+            Some(diagnostic) => Err(ControlMapError::ParseError(diagnostic)),
+            None => Ok(control_map),
         }
     }
 
+    /// ControlMap from text input, keeping broken lines as [`Line::Invalid`].
+    ///
+    /// Returns the partial ControlMap together with the diagnostics of every broken line.
+    pub fn from_txt_tolerant(txt: &str) -> (Self, Vec<Diagnostic>) {
+        let (lines, diagnostics) = control_map_parser_tolerant(txt);
+        (Self { lines }, diagnostics)
+    }
+
+    /// ControlMap from text input whose key id fields may also name keys or give decimal
+    /// codes, e.g. `LShift`, `Mouse1`, `AButton` or `42`. They are written back in canonical
+    /// hex; see [`Device::code_from_name`] for the names.
+    ///
+    /// # Errors
+    /// Same as [`ControlMap::from_txt`]; diagnostics point into `txt`.
+    ///
+    /// # Examples
+    /// ```
+    /// use controlmap_parser::{ControlMap, ControlMapError};
+    ///
+    /// let control_map =
+    ///     ControlMap::from_txt_lenient("Sprint\tLAlt\tMouse2\tAButton+LB,9\t1\t1\t1\t0x801\r\n")?;
+    /// assert_eq!(
+    ///     control_map.to_string(),
+    ///     "Sprint\t0x38\t0x1\t0x1000+0x100,0x9\t1\t1\t1\t0x801\n"
+    /// );
+    ///
+    /// let input = "Sprint\tLShift\t0xzz\t0xff\t1\t1\t1\n";
+    /// let Err(ControlMapError::ParseError(diagnostic)) = ControlMap::from_txt_lenient(input) else {
+    ///     panic!("expected a parse error");
+    /// };
+    /// assert_eq!((diagnostic.line, diagnostic.column), (1, 15));
+    /// assert_eq!(&input[diagnostic.span], "0xzz");
+    /// # Ok::<(), ControlMapError>(())
+    /// ```
+    pub fn from_txt_lenient(txt: &str) -> Result<Self> {
+        let normalized = NormalizedKeys::new(txt);
+        Self::from_txt(&normalized.text).map_err(|err| match err {
+            ControlMapError::ParseError(diagnostic) => {
+                let offset = normalized.original_offset(diagnostic.span.start);
+                let located = Diagnostic::new(txt, offset, diagnostic.code);
+                ControlMapError::ParseError(Diagnostic {
+                    line: located.line,
+                    column: located.column,
+                    span: located.span,
+                    ..diagnostic
+                })
+            }
+            err => err,
+        })
+    }
+
+    pub(crate) fn from_lines(lines: Vec<Line>) -> Self {
+        Self { lines }
+    }
+
     /// Returns an iterator over the slice.
     ///
     /// The iterator yields all items from start to end.
     pub fn iter(&self) -> Iter<'_, Line> {
         self.lines.iter()
     }
+
+    /// Returns an iterator that allows modifying each event line.
+    ///
+    /// Only events are handed out so that the blank lines separating contexts stay in place.
+    pub fn events_mut(&mut self) -> impl Iterator<Item = &mut EventLine> {
+        self.lines.iter_mut().filter_map(|line| match line {
+            Line::EventLine(event) => Some(event),
+            _ => None,
+        })
+    }
 }
 
 impl IntoIterator for ControlMap {
@@ -63,9 +125,61 @@ impl IntoIterator for ControlMap {
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ControlMapError {
     #[error("ParseError. Reason:\n{0}")]
-    ParseError(String),
+    ParseError(Diagnostic),
+    /// No longer returned: input the parser stops on is reported as [`Self::ParseError`].
+    #[deprecated(note = "parse failures are reported as `ParseError`")]
     #[error("Incomplete parse.  Remain:\n{0}")]
     Incomplete(String),
     #[error(transparent)]
     ScanCodeError(#[from] ScanCodeError),
+    #[error("Context not found: {0}")]
+    ContextNotFound(ContextKind),
+    #[error("Event `{event}` not found in context `{context}`")]
+    EventNotFound { context: ContextKind, event: String },
+    #[error("Event `{event}` already exists in context `{context}`")]
+    DuplicateEvent { context: ContextKind, event: String },
+    #[error("Index {index} is out of range for context `{context}` with {len} events")]
+    IndexOutOfRange {
+        context: ContextKind,
+        index: usize,
+        len: usize,
+    },
+    #[error(
+        "Line {line}{} would not read back as written",
+        event.as_ref().map(|event| format!(" (`{event}`)")).unwrap_or_default()
+    )]
+    NotReadBack { line: usize, event: Option<String> },
+    #[error("Event `{event}` in context `{context}` cannot be remapped on the {device}")]
+    NotRemappable {
+        context: ContextKind,
+        event: String,
+        device: Device,
+    },
+    #[error("Key {key} of the {device} is reserved by `{event}` in context `{context}`")]
+    KeyReserved {
+        context: ContextKind,
+        event: String,
+        device: Device,
+        key: KeyCode,
+    },
+    #[error("Event `{event}`: unknown {field} `{value}`")]
+    UnknownName {
+        event: String,
+        /// What `value` should have named, e.g. `keyboard key` or `flag`.
+        field: String,
+        value: String,
+    },
+    #[error("{code:#x} is not a key of the {device}")]
+    UnknownKey { device: Device, code: u32 },
+    #[error("Alias cycle: {}", chain.join(" -> "))]
+    AliasCycle { chain: Vec<String> },
+    #[error("Alias `!0,{alias}` does not name an event of Main Gameplay")]
+    DanglingAlias { alias: String, chain: Vec<String> },
+    #[error("Invalid patch statement at line {line}: {message}")]
+    InvalidPatch { line: usize, message: String },
+    #[cfg(feature = "json-patch")]
+    #[error("JSON patch could not be applied: {0}")]
+    JsonPatch(String),
+    #[error("The number of contexts would change from {expected} to {found}")]
+    ContextCountChanged { expected: usize, found: usize },
 }
@@ -0,0 +1,145 @@
+//! Player overrides in `ControlMap_Custom.txt`.
+//!
+//! Skyrim SE keeps in-game rebinds in a separate file with the same grammar as
+//! `controlmap.txt`. Contexts are still separated by blank lines, but only the rebound
+//! events are listed, and the game only reads the bindings of devices the base map lets the
+//! player remap.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{context::ContextKind, parser::KeyID, scan_code::Device, ControlMap};
+//!
+//! let base = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+//! let mut desired = base.clone();
+//! // Sneak on Left Ctrl instead of Caps Lock.
+//! let previous = desired.set_binding(ContextKind::MainGameplay, "Sneak", Device::Keyboard, KeyID::One("0x1d".parse()?))?;
+//! assert_eq!(previous, KeyID::One("0x3a".parse()?));
+//! // Forward cannot be remapped on a gamepad, so this is left out.
+//! desired.set_binding(ContextKind::MainGameplay, "Forward", Device::Gamepad, KeyID::One("0x1".parse()?))?;
+//!
+//! let custom = base.custom_overrides(&desired);
+//! assert_eq!(
+//!     custom.to_string(),
+//!     "// Main Gameplay\nSneak\t0x1d\t0xff\t0x0200+0x0040\t1\t1\t1\t0x881\n"
+//! );
+//!
+//! let effective = base.apply_custom(&custom);
+//! // Only the locked gamepad binding of Forward is left.
+//! assert_eq!(effective.diff(&desired).changes.len(), 1);
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::controlmap::ControlMap;
+use crate::parser::{EventLine, Line};
+use crate::scan_code::Device;
+
+impl ControlMap {
+    /// Smallest `ControlMap_Custom.txt` that turns `self` into `desired` as far as the player
+    /// may: events whose remappable bindings differ, in context order.
+    ///
+    /// Each listed event is the base event with its remappable bindings taken from
+    /// `desired`. Contexts without overrides are kept as empty sections so that the following
+    /// ones stay in place, and trailing ones are dropped.
+    pub fn custom_overrides(&self, desired: &ControlMap) -> ControlMap {
+        let desired_contexts = desired.contexts();
+        let mut sections: Vec<Vec<Line>> = Vec::new();
+
+        for (base_context, desired_context) in self.contexts().iter().zip(&desired_contexts) {
+            let mut section = Vec::new();
+            for event in &base_context.events {
+                let Some(wanted) = desired_context.event(&event.event_name) else {
+                    continue;
+                };
+                let overridden = with_remappable_bindings(event, wanted);
+                if overridden != **event {
+                    section.push(Line::EventLine(overridden));
+                }
+            }
+            if !section.is_empty() {
+                if let Some(header) = base_context.header {
+                    section.insert(0, Line::Comment(format!(" {}", header)));
+                }
+            }
+            sections.push(section);
+        }
+
+        while sections.last().is_some_and(Vec::is_empty) {
+            sections.pop();
+        }
+        let mut lines = Vec::new();
+        for (idx, section) in sections.into_iter().enumerate() {
+            if idx > 0 {
+                lines.push(Line::BlankLine);
+            }
+            lines.extend(section);
+        }
+        ControlMap::from_lines(lines)
+    }
+
+    /// The map the game uses when `custom` is the player's `ControlMap_Custom.txt`.
+    ///
+    /// Like the game, events unknown to `self` and bindings of devices `self` does not let
+    /// the player remap are ignored.
+    pub fn apply_custom(&self, custom: &ControlMap) -> ControlMap {
+        let mut effective = self.clone();
+        for context in custom.contexts() {
+            for event in context.events {
+                let Ok(position) = effective.find_event(context.kind, &event.event_name) else {
+                    continue;
+                };
+                if let Line::EventLine(current) = &mut effective.lines[position] {
+                    *current = with_remappable_bindings(current, event);
+                }
+            }
+        }
+        effective
+    }
+}
+
+/// `base` with the bindings of `other` on the devices `base` allows remapping.
+fn with_remappable_bindings(base: &EventLine, other: &EventLine) -> EventLine {
+    let mut event = base.clone();
+    for device in Device::ALL {
+        if base.remappable(device) {
+            *event.key_id_mut(device) = other.key_id(device).clone();
+        }
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ContextKind;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_sections_and_locked_devices() {
+        let base = ControlMap::from_txt(
+            "// Main Gameplay\nJump\t0x39\t0xff\t0x1000\t1\t1\t1\n\n// Menu Mode\nAccept\t0x1c\t0xff\t0x1000\t0\t0\t1\n\n// Console\nPick\t0xff\t0x0\t0xff\t0\t0\t0\n",
+        )
+        .unwrap();
+        let desired = ControlMap::from_txt(
+            "Jump\t0x39\t0xff\t0x1000\t1\t1\t1\n\nAccept\t0x1d\t0xff\t0x2000\t0\t0\t1\n\nPick\t0xff\t0x1\t0xff\t0\t0\t0\n",
+        )
+        .unwrap();
+
+        let custom = base.custom_overrides(&desired);
+        assert_eq!(
+            custom.to_string(),
+            "\n// Menu Mode\nAccept\t0x1c\t0xff\t0x2000\t0\t0\t1\t\n"
+        );
+        assert!(base.custom_overrides(&base).to_string().is_empty());
+
+        // A stale override of a locked device is ignored, as in game.
+        let stale = ControlMap::from_txt("Jump\t0x2c\t0xff\t0x1000\t0\t0\t0\n\nAccept\t0x1d\t0xff\t0x2000\t1\t1\t1\nExtra\t0x1\t0xff\t0xff\t1\t1\t1\n").unwrap();
+        let effective = base.apply_custom(&stale);
+        assert_eq!(
+            effective.context(ContextKind::MainGameplay).unwrap().events[0]
+                .keyboard_id
+                .to_string(),
+            "0x2c"
+        );
+        assert_eq!(effective.diff(&base).changes.len(), 2);
+    }
+}
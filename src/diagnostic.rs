@@ -0,0 +1,285 @@
+//! Structured parse diagnostics.
+//!
+//! A [`Diagnostic`] carries the line, column and byte span of the field that broke in
+//! `controlmap.txt`, so tools can point users at it instead of dumping nom's context chain.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{diagnostic::DiagnosticCode, ControlMap, ControlMapError};
+//!
+//! let input = "// Main Gameplay\nForward\t0x11\t0xzz\t0xff\t1\t1\t0\t0x801\n";
+//! let Err(ControlMapError::ParseError(diagnostic)) = ControlMap::from_txt(input) else {
+//!     panic!("expected a parse error");
+//! };
+//!
+//! assert_eq!(diagnostic.code, DiagnosticCode::InvalidMouseId);
+//! assert_eq!((diagnostic.line, diagnostic.column), (2, 14));
+//! assert_eq!(&input[diagnostic.span.clone()], "0xzz");
+//! assert_eq!(
+//!     diagnostic.render(input),
+//!     r#"error[E0003]: invalid mouse id
+//!  --> 2:14
+//!   |
+//! 2 | Forward 0x11    0xzz    0xff    1   1   0   0x801
+//!   |                 ^^^^ expected hexadecimal key code (e.g. `0xff`), key combination (e.g. `0x2a+0x0f`), alternative keys (e.g. `0x02,0x4f`) or alias (e.g. `!0,Activate`)
+//! "#
+//! );
+//! ```
+use core::fmt;
+use core::ops::Range;
+use nom::error::{VerboseError, VerboseErrorKind};
+
+/// Width of a tab stop when rendering source lines.
+const TAB_WIDTH: usize = 4;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Which part of a line could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiagnosticCode {
+    /// `E0001`
+    InvalidEventName,
+    /// `E0002`
+    InvalidKeyboardId,
+    /// `E0003`
+    InvalidMouseId,
+    /// `E0004`
+    InvalidGamepadId,
+    /// `E0005`
+    InvalidRemapKey,
+    /// `E0006`
+    InvalidRemapMouse,
+    /// `E0007`
+    InvalidRemapGamepad,
+    /// `E0008`
+    InvalidEventBinaryFlag,
+    /// `E0009`
+    UnexpectedToken,
+}
+
+const KEY_ID_EXPECTED: &[&str] = &[
+    "hexadecimal key code (e.g. `0xff`)",
+    "key combination (e.g. `0x2a+0x0f`)",
+    "alternative keys (e.g. `0x02,0x4f`)",
+    "alias (e.g. `!0,Activate`)",
+];
+
+impl DiagnosticCode {
+    const ALL: [Self; 9] = [
+        Self::InvalidEventName,
+        Self::InvalidKeyboardId,
+        Self::InvalidMouseId,
+        Self::InvalidGamepadId,
+        Self::InvalidRemapKey,
+        Self::InvalidRemapMouse,
+        Self::InvalidRemapGamepad,
+        Self::InvalidEventBinaryFlag,
+        Self::UnexpectedToken,
+    ];
+
+    /// Stable error code. e.g. `E0003`
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::InvalidEventName => "E0001",
+            Self::InvalidKeyboardId => "E0002",
+            Self::InvalidMouseId => "E0003",
+            Self::InvalidGamepadId => "E0004",
+            Self::InvalidRemapKey => "E0005",
+            Self::InvalidRemapMouse => "E0006",
+            Self::InvalidRemapGamepad => "E0007",
+            Self::InvalidEventBinaryFlag => "E0008",
+            Self::UnexpectedToken => "E0009",
+        }
+    }
+
+    /// Name of the field, used as the nom `context` label by the parser.
+    pub(crate) const fn label(&self) -> &'static str {
+        match self {
+            Self::InvalidEventName => "event name",
+            Self::InvalidKeyboardId => "keyboard id",
+            Self::InvalidMouseId => "mouse id",
+            Self::InvalidGamepadId => "gamepad id",
+            Self::InvalidRemapKey => "remap key",
+            Self::InvalidRemapMouse => "remap mouse",
+            Self::InvalidRemapGamepad => "remap gamepad",
+            Self::InvalidEventBinaryFlag => "event binary flag",
+            Self::UnexpectedToken => "end of line",
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|code| code.label() == label)
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Self::UnexpectedToken => "unexpected token".into(),
+            _ => format!("invalid {}", self.label()),
+        }
+    }
+
+    fn expected(&self) -> &'static [&'static str] {
+        match self {
+            Self::InvalidEventName => &["event name followed by a tab"],
+            Self::InvalidKeyboardId | Self::InvalidMouseId | Self::InvalidGamepadId => {
+                KEY_ID_EXPECTED
+            }
+            Self::InvalidRemapKey | Self::InvalidRemapMouse | Self::InvalidRemapGamepad => {
+                &["`0`", "`1`"]
+            }
+            Self::InvalidEventBinaryFlag => &["hexadecimal event binary flag (e.g. `0x801`)"],
+            Self::UnexpectedToken => &["end of line"],
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A problem found in `controlmap.txt`, located by line, column and byte span.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnostic {
+    pub code: DiagnosticCode,
+    pub severity: Severity,
+    pub message: String,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, counted in chars.
+    pub column: usize,
+    /// Byte range in the source of the offending field.
+    pub span: Range<usize>,
+    /// Human readable descriptions of what would have been accepted.
+    pub expected: Vec<String>,
+}
+
+impl Diagnostic {
+    /// Create an error diagnostic for the field starting at byte `offset` of `source`.
+    pub fn new(source: &str, offset: usize, code: DiagnosticCode) -> Self {
+        let offset = offset.min(source.len());
+        let line_start = source[..offset].rfind('\n').map_or(0, |pos| pos + 1);
+        let field_len = source[offset..]
+            .find(['\t', '\r', '\n'])
+            .unwrap_or(source.len() - offset);
+
+        Self {
+            code,
+            severity: Severity::Error,
+            message: code.message(),
+            line: source[..offset].matches('\n').count() + 1,
+            column: source[line_start..offset].chars().count() + 1,
+            span: offset..offset + field_len,
+            expected: code.expected().iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Convert nom's error, which must point into `source`.
+    pub(crate) fn from_nom(source: &str, err: nom::Err<VerboseError<&str>>) -> Self {
+        let err = match err {
+            nom::Err::Error(err) | nom::Err::Failure(err) => err,
+            nom::Err::Incomplete(_) => {
+                return Self::new(source, source.len(), DiagnosticCode::UnexpectedToken)
+            }
+        };
+        let offset_of = |input: &str| source.len() - input.len();
+
+        // The outermost field context knows where the broken field begins.
+        let field = err
+            .errors
+            .iter()
+            .rev()
+            .find_map(|(input, kind)| match kind {
+                VerboseErrorKind::Context(label) => {
+                    DiagnosticCode::from_label(label).map(|code| (*input, code))
+                }
+                _ => None,
+            });
+
+        match field {
+            Some((input, code)) => {
+                // Field contexts also wrap the separator in front of the field.
+                let input = input.trim_start_matches(['\t', ' ']);
+                Self::new(source, offset_of(input), code)
+            }
+            None => {
+                let offset = err.errors.first().map_or(0, |(input, _)| offset_of(input));
+                Self::new(source, offset, DiagnosticCode::UnexpectedToken)
+            }
+        }
+    }
+
+    /// Render rustc-style, quoting the offending line of `source` with a caret under the span.
+    pub fn render(&self, source: &str) -> String {
+        let line_start = source[..self.span.start.min(source.len())]
+            .rfind('\n')
+            .map_or(0, |pos| pos + 1);
+        let line_end = source[line_start..]
+            .find(['\r', '\n'])
+            .map_or(source.len(), |pos| line_start + pos);
+        let line = &source[line_start..line_end];
+
+        let caret_start = self.span.start.clamp(line_start, line_end) - line_start;
+        let caret_end = self.span.end.clamp(line_start, line_end) - line_start;
+        let pad = expand_tabs(&line[..caret_start]).chars().count();
+        let width = (expand_tabs(&line[..caret_end]).chars().count() - pad).max(1);
+
+        let gutter = " ".repeat(self.line.to_string().len());
+        let mut out = format!("{self}\n{gutter}--> {}:{}\n", self.line, self.column);
+        out += &format!("{gutter} |\n");
+        out += &format!("{} | {}\n", self.line, expand_tabs(line));
+        out += &format!("{gutter} | {}{}", " ".repeat(pad), "^".repeat(width));
+        if !self.expected.is_empty() {
+            out += &format!(" expected {}", join_expected(&self.expected));
+        }
+        out.push('\n');
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let width = TAB_WIDTH - expanded.chars().count() % TAB_WIDTH;
+                expanded += &" ".repeat(width);
+            }
+            c => expanded.push(c),
+        }
+    }
+    expanded
+}
+
+fn join_expected(expected: &[String]) -> String {
+    match expected {
+        [] => String::new(),
+        [one] => one.clone(),
+        [init @ .., last] => format!("{} or {}", init.join(", "), last),
+    }
+}
@@ -0,0 +1,455 @@
+//! Semantic diff of two control maps.
+//!
+//! Events are matched by context and name. Comments, blank lines and the spelling of codes
+//! (`0x000b` vs `0xb`) are not changes. Flag changes are reported as the groups added and removed.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::ControlMap;
+//!
+//! let old = ControlMap::from_txt("// Main Gameplay\nJump\t0x39\t0xff\t0x1000\t1\t1\t1\t0xc01\n")?;
+//! let new = ControlMap::from_txt(
+//!     "// Main Gameplay (modded)\nJump\t0x39\t0xff\t0x2000\t1\t1\t0\t0x0c09\nDodge\t0x38\t0xff\t0xff\t1\t1\t1\n",
+//! )?;
+//!
+//! assert_eq!(
+//!     old.diff(&new).to_string(),
+//!     "\
+//! Main Gameplay / Jump: gamepad 0x1000 -> 0x2000
+//! Main Gameplay / Jump: gamepad remap 1 -> 0
+//! Main Gameplay / Jump: flag added Menu
+//! Main Gameplay / Dodge: added
+//! "
+//! );
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::ControlMap;
+use crate::names::flag_text;
+use crate::parser::{EventLine, KeyID};
+use crate::scan_code::Device;
+use crate::user_event::UserEventFlag;
+use core::fmt;
+
+/// Changes from one map to another, in context order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Render as pretty printed JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// One change of an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Change {
+    EventAdded {
+        context: ContextKind,
+        event: String,
+    },
+    EventRemoved {
+        context: ContextKind,
+        event: String,
+    },
+    BindingChanged {
+        context: ContextKind,
+        event: String,
+        device: Device,
+        old: KeyID,
+        new: KeyID,
+    },
+    RemapChanged {
+        context: ContextKind,
+        event: String,
+        device: Device,
+        old: bool,
+        new: bool,
+    },
+    /// The user event flag groups changed. A missing flag column counts as no groups.
+    FlagChanged {
+        context: ContextKind,
+        event: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::names::flag_groups"))]
+        added: UserEventFlag,
+        #[cfg_attr(feature = "serde", serde(with = "crate::names::flag_groups"))]
+        removed: UserEventFlag,
+    },
+    /// The user event flag column changed, but one side is not a valid flag, so only the
+    /// columns as written can be compared.
+    RawFlagChanged {
+        context: ContextKind,
+        event: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// The event moved relative to the other events of its context. Indices count events only.
+    EventMoved {
+        context: ContextKind,
+        event: String,
+        old_index: usize,
+        new_index: usize,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EventAdded { context, event } => write!(f, "{context} / {event}: added"),
+            Self::EventRemoved { context, event } => write!(f, "{context} / {event}: removed"),
+            Self::BindingChanged {
+                context,
+                event,
+                device,
+                old,
+                new,
+            } => write!(f, "{context} / {event}: {device} {old} -> {new}"),
+            Self::RemapChanged {
+                context,
+                event,
+                device,
+                old,
+                new,
+            } => write!(
+                f,
+                "{context} / {event}: {device} remap {} -> {}",
+                *old as u8, *new as u8
+            ),
+            Self::FlagChanged {
+                context,
+                event,
+                added,
+                removed,
+            } => {
+                write!(f, "{context} / {event}: flag")?;
+                if !added.is_empty() {
+                    write!(f, " added {}", flag_text(*added))?;
+                }
+                if !added.is_empty() && !removed.is_empty() {
+                    write!(f, ",")?;
+                }
+                if !removed.is_empty() {
+                    write!(f, " removed {}", flag_text(*removed))?;
+                }
+                Ok(())
+            }
+            Self::RawFlagChanged {
+                context,
+                event,
+                old,
+                new,
+            } => write!(
+                f,
+                "{context} / {event}: flag {} -> {}",
+                old.as_deref().unwrap_or("none"),
+                new.as_deref().unwrap_or("none")
+            ),
+            Self::EventMoved {
+                context,
+                event,
+                old_index,
+                new_index,
+            } => write!(f, "{context} / {event}: moved {old_index} -> {new_index}"),
+        }
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+impl ControlMap {
+    /// Changes that turn `self` into `other`.
+    pub fn diff(&self, other: &ControlMap) -> Diff {
+        let old_contexts = self.contexts();
+        let new_contexts = other.contexts();
+        let mut changes = Vec::new();
+
+        for index in 0..old_contexts.len().max(new_contexts.len()) {
+            let context = ContextKind::from_index(index);
+            let old = old_contexts
+                .get(index)
+                .map(|c| keyed(&c.events))
+                .unwrap_or_default();
+            let new = new_contexts
+                .get(index)
+                .map(|c| keyed(&c.events))
+                .unwrap_or_default();
+            diff_context(context, &old, &new, &mut changes);
+        }
+        Diff { changes }
+    }
+}
+
+/// Events keyed by name and occurrence, so duplicated names pair up in order.
+type Keyed<'a> = Vec<((&'a str, usize), &'a EventLine)>;
+
+fn keyed<'a>(events: &[&'a EventLine]) -> Keyed<'a> {
+    let mut keyed: Keyed<'a> = Vec::with_capacity(events.len());
+    for &event in events {
+        let name = event.event_name.as_str();
+        let nth = keyed
+            .iter()
+            .filter(|((other, _), _)| *other == name)
+            .count();
+        keyed.push(((name, nth), event));
+    }
+    keyed
+}
+
+fn diff_context(context: ContextKind, old: &Keyed, new: &Keyed, changes: &mut Vec<Change>) {
+    let find = |events: &Keyed, key| events.iter().position(|(other, _)| *other == key);
+
+    for (key, _) in old {
+        if find(new, *key).is_none() {
+            changes.push(Change::EventRemoved {
+                context,
+                event: key.0.into(),
+            });
+        }
+    }
+
+    for (key, new_event) in new {
+        let Some(old_idx) = find(old, *key) else {
+            changes.push(Change::EventAdded {
+                context,
+                event: key.0.into(),
+            });
+            continue;
+        };
+        let old_event = old[old_idx].1;
+        let event = || key.0.to_string();
+
+        for device in Device::ALL {
+            if old_event.key_id(device) != new_event.key_id(device) {
+                changes.push(Change::BindingChanged {
+                    context,
+                    event: event(),
+                    device,
+                    old: old_event.key_id(device).clone(),
+                    new: new_event.key_id(device).clone(),
+                });
+            }
+        }
+        for device in Device::ALL {
+            if old_event.remappable(device) != new_event.remappable(device) {
+                changes.push(Change::RemapChanged {
+                    context,
+                    event: event(),
+                    device,
+                    old: old_event.remappable(device),
+                    new: new_event.remappable(device),
+                });
+            }
+        }
+        match (old_event.user_event_flag(), new_event.user_event_flag()) {
+            (Ok(old), Ok(new)) => {
+                let old = old.unwrap_or(UserEventFlag::empty());
+                let new = new.unwrap_or(UserEventFlag::empty());
+                if old != new {
+                    changes.push(Change::FlagChanged {
+                        context,
+                        event: event(),
+                        added: new.difference(old),
+                        removed: old.difference(new),
+                    });
+                }
+            }
+            _ if old_event.event_binary_flag != new_event.event_binary_flag => {
+                changes.push(Change::RawFlagChanged {
+                    context,
+                    event: event(),
+                    old: old_event.event_binary_flag.clone(),
+                    new: new_event.event_binary_flag.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // Events kept in both maps but outside their longest common ordering have moved.
+    let old_common: Vec<_> = old
+        .iter()
+        .filter(|(key, _)| find(new, *key).is_some())
+        .map(|(key, _)| *key)
+        .collect();
+    let new_common: Vec<_> = new
+        .iter()
+        .filter(|(key, _)| find(old, *key).is_some())
+        .map(|(key, _)| *key)
+        .collect();
+    let unmoved = longest_common_subsequence(&old_common, &new_common);
+    for key in new_common.iter().filter(|key| !unmoved.contains(key)) {
+        changes.push(Change::EventMoved {
+            context,
+            event: key.0.into(),
+            old_index: find(old, *key).unwrap_or_default(),
+            new_index: find(new, *key).unwrap_or_default(),
+        });
+    }
+}
+
+fn longest_common_subsequence<T: PartialEq + Copy>(a: &[T], b: &[T]) -> Vec<T> {
+    let (n, m) = (a.len(), b.len());
+    let mut table = vec![vec![0_usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = match a[i] == b[j] {
+                true => table[i + 1][j + 1] + 1,
+                false => table[i + 1][j].max(table[i][j + 1]),
+            };
+        }
+    }
+
+    let mut common = Vec::with_capacity(table[0][0]);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            common.push(a[i]);
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    common
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_diff() {
+        let old = ControlMap::from_txt(
+            "A\t0x1\t0xff\t0xff\t1\t1\t1\t0x8\nB\t0x2\t0xff\t0xff\t1\t1\t1\nC\t0x3\t0xff\t0xff\t1\t1\t1\n\nD\t0x4\t0xff\t0xff\t0\t0\t0\n",
+        )
+        .unwrap();
+        let new = ControlMap::from_txt(
+            "// reordered\nB\t0x0002\t0xff\t0xff\t1\t1\t1\nC\t0x3\t0xff\t0xff\t1\t1\t1\nA\t0x1\t0xff\t0xff\t1\t1\t1\t0x108\n\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            old.diff(&new).changes,
+            [
+                Change::FlagChanged {
+                    context: ContextKind::MainGameplay,
+                    event: "A".into(),
+                    added: UserEventFlag::MAIN_FOUR,
+                    removed: UserEventFlag::empty(),
+                },
+                Change::EventMoved {
+                    context: ContextKind::MainGameplay,
+                    event: "A".into(),
+                    old_index: 0,
+                    new_index: 2,
+                },
+                Change::EventRemoved {
+                    context: ContextKind::MenuMode,
+                    event: "D".into(),
+                },
+            ]
+        );
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn test_flag_changes() {
+        let old = ControlMap::from_txt(
+            "A\t0x1\t0xff\t0xff\t1\t1\t1\t0x0408\nB\t0x2\t0xff\t0xff\t1\t1\t1\t0x1\n",
+        )
+        .unwrap();
+        let mut new = ControlMap::from_txt(
+            "A\t0x1\t0xff\t0xff\t1\t1\t1\t0x401\nB\t0x2\t0xff\t0xff\t1\t1\t1\n",
+        )
+        .unwrap();
+        new.events_mut().nth(1).unwrap().event_binary_flag = Some("0xzz".into());
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.changes,
+            [
+                Change::FlagChanged {
+                    context: ContextKind::MainGameplay,
+                    event: "A".into(),
+                    added: UserEventFlag::MOVEMENT,
+                    removed: UserEventFlag::MENU,
+                },
+                Change::RawFlagChanged {
+                    context: ContextKind::MainGameplay,
+                    event: "B".into(),
+                    old: Some("0x1".into()),
+                    new: Some("0xzz".into()),
+                },
+            ]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "\
+Main Gameplay / A: flag added Movement, removed Menu
+Main Gameplay / B: flag 0x1 -> 0xzz
+"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_diff_json() {
+        let old = ControlMap::from_txt("A\t0x1\t0xff\t0xff\t1\t1\t1\n").unwrap();
+        let new = ControlMap::from_txt("A\t0x1\t0x2\t0xff\t1\t1\t1\t0x108\n").unwrap();
+        let diff = old.diff(&new);
+        let json = diff.to_json().unwrap();
+        assert_eq!(
+            json,
+            r#"{
+  "changes": [
+    {
+      "BindingChanged": {
+        "context": "MainGameplay",
+        "event": "A",
+        "device": "Mouse",
+        "old": {
+          "One": "0xff"
+        },
+        "new": {
+          "One": "0x2"
+        }
+      }
+    },
+    {
+      "FlagChanged": {
+        "context": "MainGameplay",
+        "event": "A",
+        "added": [
+          "Menu",
+          "MainFour"
+        ],
+        "removed": []
+      }
+    }
+  ]
+}"#
+        );
+        assert_eq!(serde_json::from_str::<Diff>(&json).unwrap(), diff);
+    }
+}
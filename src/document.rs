@@ -0,0 +1,271 @@
+//! Minimal-diff editing of `controlmap.txt`.
+//!
+//! [`Document`] remembers the original text of every line. Edit its [`ControlMap`] and print
+//! it: untouched lines come out exactly as they were read, and only changed lines are
+//! rewritten, padded with tabs so their fields stay in the original columns.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{document::Document, parser::KeyID};
+//!
+//! let input = "// Main Gameplay\r
+//! Jump\t\t\t\t0x39\t\t0xff\t0x1000\t\t\t1\t1\t1\t0xc01\r
+//! Sprint\t\t\t\t0x2a\t\t0xff\t0x0040\t\t\t1\t1\t1\t0x801\r
+//! ";
+//! let mut document = Document::from_txt(input)?;
+//!
+//! for event in document.control_map_mut().events_mut() {
+//!     if event.event_name == "Sprint" {
+//!         event.keyboard_id = KeyID::One("0x0038".parse().unwrap());
+//!     }
+//! }
+//!
+//! assert_eq!(
+//!     document.to_string(),
+//!     "// Main Gameplay\r
+//! Jump\t\t\t\t0x39\t\t0xff\t0x1000\t\t\t1\t1\t1\t0xc01\r
+//! Sprint\t\t\t\t0x0038\t\t0xff\t0x0040\t\t\t1\t1\t1\t0x801\r
+//! "
+//! );
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::controlmap::{ControlMap, ControlMapError, Result};
+use crate::parser::cst::{SyntaxLine, SyntaxTree};
+use crate::parser::{EventLine, Line};
+use core::fmt;
+
+/// Tab stop width the vanilla file is aligned with.
+const TAB_WIDTH: usize = 4;
+
+/// A [`ControlMap`] that remembers the formatting it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    tree: SyntaxTree,
+    control_map: ControlMap,
+}
+
+impl Document {
+    /// Parse text, keeping its formatting.
+    ///
+    /// # Errors
+    /// The first line that could not be parsed is reported as a diagnostic.
+    pub fn from_txt(txt: &str) -> Result<Self> {
+        let tree = SyntaxTree::parse(txt);
+        if let Some(diagnostic) = tree.diagnostics().next() {
+            return Err(ControlMapError::ParseError(diagnostic.clone()));
+        }
+
+        let control_map = ControlMap::from_lines(tree.to_lines());
+        Ok(Self { tree, control_map })
+    }
+
+    pub fn control_map(&self) -> &ControlMap {
+        &self.control_map
+    }
+
+    /// Edit the lines. Changes are rendered by [`Document`]'s `Display`.
+    pub fn control_map_mut(&mut self) -> &mut ControlMap {
+        &mut self.control_map
+    }
+
+    pub fn into_control_map(self) -> ControlMap {
+        self.control_map
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let original = self.tree.lines();
+        let current: Vec<&Line> = self.control_map.iter().collect();
+        let default_ending = match original.first().map(SyntaxLine::line_ending) {
+            Some(ending) if !ending.is_empty() => ending,
+            _ => "\n",
+        };
+
+        let mut rendered: Vec<(String, &str)> = Vec::with_capacity(current.len());
+        let mut template = None;
+        let mut prev = (0, 0);
+        let matches = longest_common_subsequence(original, &current);
+        let end = (original.len(), current.len());
+
+        for (orig_idx, cur_idx) in matches.into_iter().chain([end]) {
+            let mut unused: Vec<&SyntaxLine> = original[prev.0..orig_idx].iter().collect();
+            for (nth, line) in current[prev.1..cur_idx].iter().enumerate() {
+                let body = match line {
+                    Line::EventLine(event) => {
+                        // Prefer the same event, then whatever stood at this position.
+                        let same = unused.iter().position(|orig| match orig.line() {
+                            Line::EventLine(orig) => orig.event_name == event.event_name,
+                            _ => false,
+                        });
+                        let replaced = same.or_else(|| {
+                            original[prev.0..orig_idx]
+                                .get(nth)
+                                .and_then(|orig| {
+                                    unused.iter().position(|u| core::ptr::eq(*u, orig))
+                                })
+                                .filter(|&pos| !unused[pos].tokens().is_empty())
+                        });
+                        if let Some(pos) = replaced {
+                            template = Some(unused.remove(pos));
+                        }
+                        format_aligned(event, template)
+                    }
+                    line => line.to_string().trim_end_matches('\n').to_string(),
+                };
+                let ending = template.map_or(default_ending, SyntaxLine::line_ending);
+                rendered.push((body, ending));
+            }
+
+            if let Some(orig) = original.get(orig_idx) {
+                if !orig.tokens().is_empty() {
+                    template = Some(orig);
+                }
+                rendered.push((orig.node().to_string(), orig.line_ending()));
+            }
+            prev = (orig_idx + 1, cur_idx + 1);
+        }
+
+        let last = rendered.len().saturating_sub(1);
+        for (idx, (body, ending)) in rendered.iter().enumerate() {
+            // Only the last line may go without a line ending.
+            let ending = match ending.is_empty() && idx != last {
+                true => default_ending,
+                false => ending,
+            };
+            write!(f, "{}{}", body, ending)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the fields of `event`, padding with tabs up to the columns used by `template`.
+fn format_aligned(event: &EventLine, template: Option<&SyntaxLine>) -> String {
+    let mut fields = vec![
+        event.event_name.clone(),
+        event.keyboard_id.to_string(),
+        event.mouse_id.to_string(),
+        event.gamepad_id.to_string(),
+        (event.remap_key as u8).to_string(),
+        (event.remap_mouse as u8).to_string(),
+        (event.remap_gamepad as u8).to_string(),
+    ];
+    fields.extend(event.event_binary_flag.clone());
+
+    let mut columns = Vec::new();
+    let mut column = 0;
+    for token in template.map(SyntaxLine::tokens).unwrap_or_default() {
+        columns.push(column);
+        column = advance(column, token.text());
+        column = advance(column, token.trailing());
+    }
+
+    let mut out = String::new();
+    let mut column = 0;
+    for (idx, field) in fields.iter().enumerate() {
+        if idx > 0 {
+            let target = columns.get(idx).copied().unwrap_or(0);
+            loop {
+                out.push('\t');
+                column = advance(column, "\t");
+                if column >= target {
+                    break;
+                }
+            }
+        }
+        out += field;
+        column = advance(column, field);
+    }
+    out
+}
+
+/// Display column after writing `text` from `column`.
+fn advance(column: usize, text: &str) -> usize {
+    text.chars().fold(column, |column, c| match c {
+        '\t' => (column / TAB_WIDTH + 1) * TAB_WIDTH,
+        _ => column + 1,
+    })
+}
+
+/// Index pairs of the lines left untouched, in order.
+fn longest_common_subsequence(original: &[SyntaxLine], current: &[&Line]) -> Vec<(usize, usize)> {
+    let (n, m) = (original.len(), current.len());
+    let mut table = vec![vec![0_usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = match original[i].line() == current[j] {
+                true => table[i + 1][j + 1] + 1,
+                false => table[i + 1][j].max(table[i][j + 1]),
+            };
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(table[0][0]);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if original[i].line() == current[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_untouched_document_is_unchanged() {
+        let input = include_str!("../examples/controlmap.txt");
+        let document = Document::from_txt(input).unwrap();
+        assert_eq!(document.to_string(), input);
+    }
+
+    #[test]
+    fn test_only_edited_lines_change() {
+        let input = include_str!("../examples/controlmap.txt");
+        let mut document = Document::from_txt(input).unwrap();
+        for event in document.control_map_mut().events_mut() {
+            if event.event_name == "Screenshot" {
+                event.remap_key = true;
+                event.event_binary_flag = Some("0x8".into());
+            }
+        }
+
+        let expected = input.replace(
+            "Screenshot\t\t\t0xb7\t\t0xff\t0xff\t\t\t0\t0\t0\r\n",
+            "Screenshot\t\t\t0xb7\t\t0xff\t0xff\t\t\t1\t0\t0\t0x8\r\n",
+        );
+        assert_eq!(document.to_string(), expected);
+    }
+
+    #[test]
+    fn test_inserted_and_removed_lines() {
+        let input = "A\t\t0x1\t0xff\t0xff\t0\t0\t0\nLonger Name\t0x2\t0xff\t0xff\t0\t0\t0";
+        let mut document = Document::from_txt(input).unwrap();
+        let lines: Vec<Line> = document.control_map().iter().cloned().collect();
+        let Line::EventLine(mut inserted) = lines[0].clone() else {
+            unreachable!()
+        };
+        inserted.event_name = "B".into();
+
+        let mut with_inserted = lines.clone();
+        with_inserted.insert(1, Line::EventLine(inserted));
+        *document.control_map_mut() = ControlMap::from_lines(with_inserted);
+        assert_eq!(
+            document.to_string(),
+            "A\t\t0x1\t0xff\t0xff\t0\t0\t0\nB\t\t0x1\t0xff\t0xff\t0\t0\t0\nLonger Name\t0x2\t0xff\t0xff\t0\t0\t0"
+        );
+
+        *document.control_map_mut() = ControlMap::from_lines(lines[..1].to_vec());
+        assert_eq!(document.to_string(), "A\t\t0x1\t0xff\t0xff\t0\t0\t0\n");
+    }
+}
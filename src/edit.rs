@@ -0,0 +1,265 @@
+//! Structural editing that keeps the input contexts intact.
+//!
+//! These methods only ever add, remove or replace event lines, so the blank lines separating
+//! contexts, and with them the number and order of contexts, cannot change.
+use crate::context::ContextKind;
+use crate::controlmap::{ControlMap, ControlMapError, Result};
+use crate::parser::{parse_line, EventLine, KeyID, Line};
+use crate::scan_code::Device;
+
+impl ControlMap {
+    /// Append `event` to the end of `context`.
+    ///
+    /// # Errors
+    /// - The context does not exist or already has an event of the same name.
+    /// - The event would not read back as written (e.g. a tab in its name).
+    pub fn insert_event(&mut self, context: ContextKind, event: EventLine) -> Result<()> {
+        let len = self.event_indices(context)?.len();
+        self.insert_event_at(context, len, event)
+    }
+
+    /// Insert `event` so that it becomes the `index`th event of `context`.
+    ///
+    /// # Errors
+    /// Same as [`ControlMap::insert_event`], or `index` is past the end of the context.
+    ///
+    /// # Examples
+    /// ```
+    /// use controlmap_parser::{context::ContextKind, ControlMap};
+    ///
+    /// let mut control_map = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+    /// let mut event = control_map.context(ContextKind::Favor).unwrap().events[0].clone();
+    /// event.event_name = "Accept".into();
+    /// control_map.insert_event_at(ContextKind::Favor, 0, event)?;
+    ///
+    /// let favor = control_map.context(ContextKind::Favor).unwrap();
+    /// assert_eq!(favor.events[0].event_name, "Accept");
+    /// assert_eq!(control_map.contexts().len(), 17);
+    /// # Ok::<(), controlmap_parser::ControlMapError>(())
+    /// ```
+    pub fn insert_event_at(
+        &mut self,
+        context: ContextKind,
+        index: usize,
+        event: EventLine,
+    ) -> Result<()> {
+        let indices = self.event_indices(context)?;
+        if self.find_event(context, &event.event_name).is_ok() {
+            return Err(ControlMapError::DuplicateEvent {
+                context,
+                event: event.event_name,
+            });
+        }
+
+        let position = match indices.get(index) {
+            Some(&position) => position,
+            None if index == indices.len() => match indices.last() {
+                Some(&last) => last + 1,
+                None => self.context_lines(context)?.end,
+            },
+            None => {
+                return Err(ControlMapError::IndexOutOfRange {
+                    context,
+                    index,
+                    len: indices.len(),
+                })
+            }
+        };
+        check_event(&event, position)?;
+        self.lines.insert(position, Line::EventLine(event));
+        Ok(())
+    }
+
+    /// Remove the event named `name` from `context`.
+    ///
+    /// # Errors
+    /// The context or event does not exist.
+    pub fn remove_event(&mut self, context: ContextKind, name: &str) -> Result<EventLine> {
+        let position = self.find_event(context, name)?;
+        match self.lines.remove(position) {
+            Line::EventLine(event) => Ok(event),
+            _ => unreachable!("find_event only returns event lines"),
+        }
+    }
+
+    /// Move the event named `name` from context `from` so that it becomes the `index`th event of
+    /// context `to`. `from` and `to` may be the same context to reorder it.
+    ///
+    /// # Errors
+    /// - Either context or the event does not exist.
+    /// - `to` already has an event of the same name.
+    /// - `index` is past the end of `to` (not counting the moved event itself).
+    pub fn move_event(
+        &mut self,
+        from: ContextKind,
+        name: &str,
+        to: ContextKind,
+        index: usize,
+    ) -> Result<()> {
+        self.find_event(from, name)?;
+        let mut len = self.event_indices(to)?.len();
+        if from == to {
+            len -= 1;
+        } else if self.find_event(to, name).is_ok() {
+            return Err(ControlMapError::DuplicateEvent {
+                context: to,
+                event: name.into(),
+            });
+        }
+        if index > len {
+            return Err(ControlMapError::IndexOutOfRange {
+                context: to,
+                index,
+                len,
+            });
+        }
+
+        let event = self.remove_event(from, name)?;
+        self.insert_event_at(to, index, event)
+    }
+
+    /// Replace the `device` binding of the event named `name` in `context`, returning the
+    /// previous binding.
+    ///
+    /// # Errors
+    /// The context or event does not exist, or the binding would not read back as written.
+    pub fn set_binding(
+        &mut self,
+        context: ContextKind,
+        name: &str,
+        device: Device,
+        key_id: KeyID,
+    ) -> Result<KeyID> {
+        let position = self.find_event(context, name)?;
+        let Line::EventLine(event) = &mut self.lines[position] else {
+            unreachable!("find_event only returns event lines");
+        };
+
+        let mut edited = event.clone();
+        let previous = core::mem::replace(edited.key_id_mut(device), key_id);
+        check_event(&edited, position)?;
+
+        *event = edited;
+        Ok(previous)
+    }
+
+    /// Line indices of the events of `context`.
+    pub(crate) fn event_indices(&self, context: ContextKind) -> Result<Vec<usize>> {
+        let lines = self.context_lines(context)?;
+        Ok(lines
+            .filter(|&idx| matches!(self.lines[idx], Line::EventLine(_)))
+            .collect())
+    }
+
+    fn context_lines(&self, context: ContextKind) -> Result<core::ops::Range<usize>> {
+        self.context(context)
+            .map(|context| context.lines)
+            .ok_or(ControlMapError::ContextNotFound(context))
+    }
+
+    /// Line index of the event named `name` in `context`.
+    pub(crate) fn find_event(&self, context: ContextKind, name: &str) -> Result<usize> {
+        self.event_indices(context)?
+            .into_iter()
+            .find(|&idx| match &self.lines[idx] {
+                Line::EventLine(event) => event.event_name == name,
+                _ => false,
+            })
+            .ok_or_else(|| ControlMapError::EventNotFound {
+                context,
+                event: name.into(),
+            })
+    }
+}
+
+/// Make sure `event`, about to be placed at line index `position`, is printed as a single line
+/// that parses back to itself.
+fn check_event(event: &EventLine, position: usize) -> Result<()> {
+    let line = Line::EventLine(event.clone());
+    match parse_line(&line.to_string()) {
+        Ok(("", parsed)) if parsed == line => Ok(()),
+        _ => Err(ControlMapError::NotReadBack {
+            line: position + 1,
+            event: Some(event.event_name.clone()),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn vanilla() -> ControlMap {
+        ControlMap::from_txt(include_str!("../examples/controlmap.txt")).unwrap()
+    }
+
+    #[test]
+    fn test_edits_keep_contexts() {
+        let mut control_map = vanilla();
+        let event = control_map
+            .remove_event(ContextKind::Stats, "Rotate")
+            .unwrap();
+        assert!(control_map
+            .context(ContextKind::Stats)
+            .unwrap()
+            .events
+            .is_empty());
+
+        control_map.insert_event(ContextKind::Stats, event).unwrap();
+        control_map
+            .move_event(ContextKind::MenuMode, "Console", ContextKind::MenuMode, 0)
+            .unwrap();
+
+        let contexts = control_map.contexts();
+        assert_eq!(contexts.len(), 17);
+        assert_eq!(contexts[ContextKind::Stats.index()].events.len(), 1);
+        assert_eq!(
+            contexts[ContextKind::MenuMode.index()].events[0].event_name,
+            "Console"
+        );
+    }
+
+    #[test]
+    fn test_edits_reject_invalid() {
+        let mut control_map = vanilla();
+        let mut event = control_map.context(ContextKind::Stats).unwrap().events[0].clone();
+
+        assert_eq!(
+            control_map.insert_event(ContextKind::Stats, event.clone()),
+            Err(ControlMapError::DuplicateEvent {
+                context: ContextKind::Stats,
+                event: "Rotate".into()
+            })
+        );
+
+        let rotate = control_map
+            .find_event(ContextKind::Stats, "Rotate")
+            .unwrap();
+        event.event_name = "Rot\nate".into();
+        assert_eq!(
+            control_map.insert_event(ContextKind::Stats, event),
+            Err(ControlMapError::NotReadBack {
+                line: rotate + 2,
+                event: Some("Rot\nate".into())
+            })
+        );
+        assert_eq!(
+            control_map.set_binding(
+                ContextKind::Stats,
+                "Rotate",
+                Device::Keyboard,
+                KeyID::Alias("Rot\tate".into())
+            ),
+            Err(ControlMapError::NotReadBack {
+                line: rotate + 1,
+                event: Some("Rotate".into())
+            })
+        );
+        assert_eq!(
+            control_map.remove_event(ContextKind::Other(17), "Rotate"),
+            Err(ControlMapError::ContextNotFound(ContextKind::Other(17)))
+        );
+        assert_eq!(control_map, vanilla());
+    }
+}
@@ -0,0 +1,192 @@
+//! Finding unused keys for new hotkeys.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{
+//!     context::ContextKind, free_key::Ranking, scan_code::KeyboardCode, ControlMap,
+//! };
+//!
+//! let control_map = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+//! let free = control_map.free_keyboard_keys(&[ContextKind::MainGameplay], Ranking::Ergonomic);
+//! assert_eq!(free[..3], [KeyboardCode::X, KeyboardCode::F, KeyboardCode::V]);
+//! assert!(!free.contains(&KeyboardCode::W));
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::ControlMap;
+use crate::parser::KeyID;
+use crate::scan_code::{Device, GamepadCode, KeyboardCode};
+use std::collections::HashSet;
+
+/// Order of the keys returned by [`ControlMap::free_keyboard_keys`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Ranking {
+    /// Every free key by scan code.
+    #[default]
+    CodeOrder,
+    /// Keys closest to WASD first, then the rest of the keyboard, then F-keys.
+    /// Media, system and IME keys are left out.
+    Ergonomic,
+}
+
+impl ControlMap {
+    /// Keyboard keys no event of `contexts` is bound to, alone, as an alternative or in a chord.
+    pub fn free_keyboard_keys(
+        &self,
+        contexts: &[ContextKind],
+        ranking: Ranking,
+    ) -> Vec<KeyboardCode> {
+        let used = self.used_codes(Device::Keyboard, contexts);
+        let free = KeyboardCode::iter()
+            .filter(|key| *key != KeyboardCode::None && !used.contains(&(key.clone() as u32)));
+
+        match ranking {
+            Ranking::CodeOrder => free.collect(),
+            Ranking::Ergonomic => {
+                let mut ranked: Vec<_> = free
+                    .filter_map(|key| ergonomic_rank(&key).map(|rank| (rank, key)))
+                    .collect();
+                ranked.sort_by_key(|(rank, _)| *rank);
+                ranked.into_iter().map(|(_, key)| key).collect()
+            }
+        }
+    }
+
+    /// Gamepad buttons no event of `contexts` is bound to.
+    ///
+    /// Only the codes `controlmap.txt` uses are considered, not the Papyrus `Input` script ones.
+    /// The thumbsticks `_360_LS`/`_360_RS` are axes, not buttons, and are left out.
+    pub fn free_gamepad_buttons(&self, contexts: &[ContextKind]) -> Vec<GamepadCode> {
+        use GamepadCode::*;
+
+        let used = self.used_codes(Device::Gamepad, contexts);
+        GamepadCode::iter()
+            .filter(|button| {
+                let code = button.clone() as u32;
+                !matches!(button, None | _360_LS | _360_RS)
+                    && !(DpadUp as u32..=RightTrigger as u32).contains(&code)
+                    && !used.contains(&code)
+            })
+            .collect()
+    }
+
+    /// Codes of `device` taking part in any binding of `contexts`, with aliases resolved.
+    fn used_codes(&self, device: Device, contexts: &[ContextKind]) -> HashSet<u32> {
+        let mut used = HashSet::new();
+        for context in self.contexts() {
+            if !contexts.contains(&context.kind) {
+                continue;
+            }
+            for event in context.events {
+                let key_id = match self.resolve_alias(event.key_id(device), device) {
+                    Ok(resolved) => resolved.key_id,
+                    Err(_) => event.key_id(device).clone(),
+                };
+                collect_codes(&key_id, &mut used);
+            }
+        }
+        used
+    }
+}
+
+fn collect_codes(key_id: &KeyID, codes: &mut HashSet<u32>) {
+    match key_id {
+        KeyID::Or(keys) | KeyID::And(keys) => {
+            for key in keys {
+                collect_codes(key, codes);
+            }
+        }
+        KeyID::One(key) => {
+            codes.insert(key.code());
+        }
+        KeyID::Alias(_) => {}
+    }
+}
+
+/// Rows of the main block of a QWERTY keyboard, with the offset of their first key in
+/// quarter key widths.
+const MAIN_BLOCK: [(usize, &[KeyboardCode]); 4] = {
+    use KeyboardCode::*;
+    [
+        (
+            0,
+            &[Tilde, _1, _2, _3, _4, _5, _6, _7, _8, _9, _0, Hyphen, Equal],
+        ),
+        (
+            6,
+            &[Q, W, E, R, T, Y, U, I, O, P, Bracketleft, Bracketright],
+        ),
+        (7, &[A, S, D, F, G, H, J, K, L, Semicolon, Quotesingle]),
+        (9, &[Z, X, C, V, B, N, M, Comma, Period, Slash]),
+    ]
+};
+
+/// Lower is easier to reach from WASD; `None` for keys not worth suggesting.
+fn ergonomic_rank(key: &KeyboardCode) -> Option<(u8, usize)> {
+    use KeyboardCode::*;
+
+    // Centre of `S`, in quarter key widths.
+    let (s_row, s_col) = (2, 7 + 4);
+    for (row, (offset, keys)) in MAIN_BLOCK.iter().enumerate() {
+        if let Some(idx) = keys.iter().position(|k| k == key) {
+            let col = offset + 4 * idx;
+            let distance = (row.abs_diff(s_row) * 4).pow(2) + col.abs_diff(s_col).pow(2);
+            return Some((0, distance));
+        }
+    }
+
+    let code = key.clone() as usize;
+    match key {
+        F1 | F2 | F3 | F4 | F5 | F6 | F7 | F8 | F9 | F10 | F11 | F12 | F13 | F14 | F15 => {
+            Some((2, code))
+        }
+        PrevTrack | NextTrack | Mute | Calc | PlayPause | MediaStop | VolMinus | VolPlus
+        | WebHome | WebSearch | WebFavorites | WebRefresh | WebStop | WebForward | WebBack
+        | MyComputer | Mail | MediaSelect | Power | Sleep | Wake | LWindows | RWindows | Apps
+        | DIK_OEM_102 | DIK_KANA | DIK_ABNT_C1 | DIK_CONVERT | DIK_NOCONVERT | DIK_ABNT_C2
+        | DIK_AT | DIK_UNDERLINE | DIK_KANJI | DIK_STOP | DIK_AX | DIK_UNLABELED | Colon
+        | Unknown | None => Option::None,
+        _ => Some((1, code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_free_keys() {
+        let control_map = ControlMap::from_txt(
+            "// Main Gameplay\nForward\t0x11\t0xff\t0x0001,0x0200+0x1000\t1\t1\t1\nMenu\t0x0f\t0xff\t!0,Forward\t1\t1\t1\n\n// Menu Mode\nSelect\t0x1f\t0xff\t0x2000\t0\t0\t0\n",
+        )
+        .unwrap();
+        let main = [ContextKind::MainGameplay];
+
+        let ranked = control_map.free_keyboard_keys(&main, Ranking::Ergonomic);
+        assert_eq!(
+            ranked[..4],
+            [
+                KeyboardCode::S,
+                KeyboardCode::A,
+                KeyboardCode::D,
+                KeyboardCode::Z
+            ]
+        );
+        assert!(!ranked.contains(&KeyboardCode::Mute));
+        assert!(!ranked.contains(&KeyboardCode::Tab));
+        assert_eq!(ranked.last(), Some(&KeyboardCode::F15));
+
+        let by_code = control_map.free_keyboard_keys(&main, Ranking::CodeOrder);
+        assert_eq!(by_code[..2], [KeyboardCode::Esc, KeyboardCode::_1]);
+
+        let buttons = control_map.free_gamepad_buttons(&main);
+        assert_eq!(buttons[0], GamepadCode::Down);
+        assert!(!buttons.contains(&GamepadCode::_360_A));
+        assert!(buttons.contains(&GamepadCode::_360_B));
+        assert!(!buttons.contains(&GamepadCode::_360_LS));
+        assert!(!buttons.contains(&GamepadCode::_360_RS));
+    }
+}
@@ -0,0 +1,358 @@
+//! Human friendly JSON.
+//!
+//! The derived serde form mirrors the Rust types (`{"keyboard_id": {"One": "0x11"}}`). This
+//! one groups events by context and names keys after the [`scan_code`](crate::scan_code)
+//! enums:
+//!
+//! - A binding is a string of keys joined by `+` for a chord, or an array of such strings for
+//!   alternatives. An alias is written `!0,Activate`, and an unmapped device is left out.
+//!   Codes not written in canonical form (`0x000b`) are kept as hex, as written.
+//! - `remappable` lists the devices the player may remap.
+//! - `flags` lists the user event groups, or is the hex value as written if it has unknown
+//!   bits or is not written in canonical form.
+//! - Comments are kept in place as `{"comment": "..."}`.
+//!
+//! Contexts are identified by position; their `name` is only informative. Reading the JSON
+//! back gives an equal [`ControlMap`]; keys given by name are written in canonical hex.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::ControlMap;
+//!
+//! let control_map = ControlMap::from_txt(
+//!     "// Main Gameplay\nForward\t0x11\t0xff\t0xff\t1\t1\t0\t0x801\nShout\t0x2c\t0xff\t0x100+0x1000,0x9\t1\t0\t1\t0x841\n",
+//! )?;
+//! let json = control_map.to_human_json().unwrap();
+//! assert_eq!(
+//!     json,
+//!     r#"{
+//!   "contexts": [
+//!     {
+//!       "name": "Main Gameplay",
+//!       "events": [
+//!         {
+//!           "comment": " Main Gameplay"
+//!         },
+//!         {
+//!           "name": "Forward",
+//!           "keyboard": "W",
+//!           "remappable": [
+//!             "keyboard",
+//!             "mouse"
+//!           ],
+//!           "flags": [
+//!             "Movement",
+//!             "Vats"
+//!           ]
+//!         },
+//!         {
+//!           "name": "Shout",
+//!           "keyboard": "Z",
+//!           "gamepad": [
+//!             "_360_LB+_360_A",
+//!             "_360_LT"
+//!           ],
+//!           "remappable": [
+//!             "keyboard",
+//!             "gamepad"
+//!           ],
+//!           "flags": [
+//!             "Movement",
+//!             "Fighting",
+//!             "Vats"
+//!           ]
+//!         }
+//!       ]
+//!     }
+//!   ]
+//! }"#
+//! );
+//! assert_eq!(ControlMap::from_human_json(&json).unwrap(), control_map);
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+//!
+//! The functions of this module fit `#[serde(with = "controlmap_parser::human")]`.
+use crate::controlmap::{ControlMap, ControlMapError};
+use crate::names::{device_from_name, flag_names, parse_flag, parse_key_id, NamedKeys};
+use crate::parser::{EventLine, KeyCode, KeyID, Line};
+use crate::scan_code::{Device, UNMAPPED};
+use crate::user_event::UserEventFlag;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HumanMap {
+    contexts: Vec<HumanContext>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HumanContext {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    events: Vec<HumanLine>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum HumanLine {
+    Event(HumanEvent),
+    Comment {
+        comment: String,
+    },
+    /// A line that did not parse, as written.
+    Invalid {
+        invalid: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HumanEvent {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keyboard: Option<HumanKeys>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mouse: Option<HumanKeys>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gamepad: Option<HumanKeys>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    remappable: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flags: Option<HumanFlags>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum HumanKeys {
+    One(String),
+    Alternatives(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum HumanFlags {
+    Names(Vec<String>),
+    Raw(String),
+}
+
+/// Serialize `control_map` in the human friendly form.
+pub fn serialize<S: Serializer>(
+    control_map: &ControlMap,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    to_human(control_map).serialize(serializer)
+}
+
+/// Deserialize a [`ControlMap`] from the human friendly form.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ControlMap, D::Error> {
+    from_human(HumanMap::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+impl ControlMap {
+    /// Render as pretty printed human friendly JSON.
+    pub fn to_human_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&to_human(self))
+    }
+
+    /// Read human friendly JSON.
+    pub fn from_human_json(json: &str) -> serde_json::Result<Self> {
+        let human: HumanMap = serde_json::from_str(json)?;
+        from_human(human).map_err(serde_json::Error::custom)
+    }
+}
+
+fn to_human(control_map: &ControlMap) -> HumanMap {
+    let lines: Vec<&Line> = control_map.iter().collect();
+    let contexts = control_map
+        .contexts()
+        .into_iter()
+        .map(|context| HumanContext {
+            name: context.kind.to_string(),
+            events: lines[context.lines]
+                .iter()
+                .filter_map(|line| match line {
+                    Line::EventLine(event) => Some(HumanLine::Event(to_human_event(event))),
+                    Line::Comment(comment) => Some(HumanLine::Comment {
+                        comment: comment.clone(),
+                    }),
+                    Line::Invalid { raw, .. } => Some(HumanLine::Invalid {
+                        invalid: raw.clone(),
+                    }),
+                    Line::BlankLine => None,
+                })
+                .collect(),
+        })
+        .collect();
+    HumanMap { contexts }
+}
+
+fn to_human_event(event: &EventLine) -> HumanEvent {
+    let keys = |device| {
+        let key_id = event.key_id(device);
+        match key_id {
+            KeyID::One(key) if key.code() == UNMAPPED && key.as_str() == "0xff" => None,
+            KeyID::Or(keys) => Some(HumanKeys::Alternatives(
+                keys.iter()
+                    .map(|key| NamedKeys(device, key).to_string())
+                    .collect(),
+            )),
+            _ => Some(HumanKeys::One(NamedKeys(device, key_id).to_string())),
+        }
+    };
+    let flags = event.event_binary_flag.as_ref().map(|raw| {
+        let names = match raw.parse::<UserEventFlag>() {
+            Ok(flag) if flag.to_string() == *raw => flag_names(flag),
+            _ => None,
+        };
+        match names {
+            Some(names) => HumanFlags::Names(names),
+            None => HumanFlags::Raw(raw.clone()),
+        }
+    });
+
+    HumanEvent {
+        name: event.event_name.clone(),
+        keyboard: keys(Device::Keyboard),
+        mouse: keys(Device::Mouse),
+        gamepad: keys(Device::Gamepad),
+        remappable: Device::ALL
+            .into_iter()
+            .filter(|&device| event.remappable(device))
+            .map(|device| device.to_string())
+            .collect(),
+        flags,
+    }
+}
+
+fn from_human(human: HumanMap) -> Result<ControlMap, ControlMapError> {
+    let mut lines = Vec::new();
+    for (idx, context) in human.contexts.into_iter().enumerate() {
+        if idx > 0 {
+            lines.push(Line::BlankLine);
+        }
+        for line in context.events {
+            lines.push(match line {
+                HumanLine::Event(event) => Line::EventLine(from_human_event(event)?),
+                HumanLine::Comment { comment } => Line::Comment(comment),
+                HumanLine::Invalid { invalid } => Line::Invalid {
+                    raw: invalid,
+                    diagnostics: Vec::new(),
+                },
+            });
+        }
+    }
+    Ok(ControlMap::from_lines(lines))
+}
+
+fn from_human_event(event: HumanEvent) -> Result<EventLine, ControlMapError> {
+    let invalid = |field: &str, value: &str| ControlMapError::UnknownName {
+        event: event.name.clone(),
+        field: field.into(),
+        value: value.into(),
+    };
+    let keys = |device: Device, keys: &Option<HumanKeys>| -> Result<KeyID, ControlMapError> {
+        let parse = |keys: &str| {
+            parse_key_id(device, keys).ok_or_else(|| invalid(&format!("{} key", device), keys))
+        };
+        match keys {
+            None => Ok(KeyID::One(KeyCode::new(UNMAPPED))),
+            Some(HumanKeys::One(keys)) => parse(keys),
+            Some(HumanKeys::Alternatives(keys)) => keys
+                .iter()
+                .map(|keys| parse(keys))
+                .collect::<Result<_, _>>()
+                .map(KeyID::Or),
+        }
+    };
+
+    let mut remappable = Vec::new();
+    for device in &event.remappable {
+        remappable.push(device_from_name(device).ok_or_else(|| invalid("device", device))?);
+    }
+    let event_binary_flag = match &event.flags {
+        None => None,
+        Some(HumanFlags::Raw(raw)) => {
+            raw.parse::<UserEventFlag>()
+                .map_err(|_| invalid("flag", raw))?;
+            Some(raw.clone())
+        }
+        Some(HumanFlags::Names(names)) => {
+            let mut flag = UserEventFlag::empty();
+            for name in names {
+                flag |= parse_flag(name).ok_or_else(|| invalid("flag", name))?;
+            }
+            Some(flag.to_string())
+        }
+    };
+
+    Ok(EventLine {
+        keyboard_id: keys(Device::Keyboard, &event.keyboard)?,
+        mouse_id: keys(Device::Mouse, &event.mouse)?,
+        gamepad_id: keys(Device::Gamepad, &event.gamepad)?,
+        remap_key: remappable.contains(&Device::Keyboard),
+        remap_mouse: remappable.contains(&Device::Mouse),
+        remap_gamepad: remappable.contains(&Device::Gamepad),
+        event_binary_flag,
+        event_name: event.name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_vanilla_round_trip() {
+        let control_map = ControlMap::from_txt(include_str!("../examples/controlmap.txt")).unwrap();
+        let json = control_map.to_human_json().unwrap();
+        let back = ControlMap::from_human_json(&json).unwrap();
+        assert_eq!(back, control_map);
+        assert_eq!(back.to_string(), control_map.to_string());
+    }
+
+    #[test]
+    fn test_papyrus_codes_stay_hex() {
+        let control_map = ControlMap::from_txt("E\t0x1\t0x100\t0x10a\t0\t0\t0\n").unwrap();
+        let json = control_map.to_human_json().unwrap();
+        assert!(json.contains(r#""mouse": "0x100""#));
+        assert!(json.contains(r#""gamepad": "0x10a""#));
+        let back = ControlMap::from_human_json(&json).unwrap();
+        assert_eq!(back, control_map);
+        assert_eq!(back.to_string(), control_map.to_string());
+    }
+
+    #[test]
+    fn test_hand_written() {
+        let json = r#"{"contexts": [
+            {"name": "Main Gameplay", "events": [
+                {"name": "Sprint", "keyboard": "lalt", "gamepad": "0x0040", "flags": ["Movement", "VATS"]},
+                {"name": "Odd", "keyboard": "!0,Sprint", "flags": "0x00801"}
+            ]},
+            {"name": "Menu Mode"}
+        ]}"#;
+        assert_eq!(
+            ControlMap::from_human_json(json).unwrap().to_string(),
+            "Sprint\t0x38\t0xff\t0x0040\t0\t0\t0\t0x801\nOdd\t!0,Sprint\t0xff\t0xff\t0\t0\t0\t0x00801\n\n"
+        );
+
+        let err = ControlMap::from_human_json(
+            r#"{"contexts": [{"events": [{"name": "Jump", "keyboard": "Spacebar"}]}]}"#,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Event `Jump`: unknown keyboard key `Spacebar`"));
+
+        let err = ControlMap::from_human_json(
+            r#"{"contexts": [{"events": [{"name": "Jump", "flags": ["Leaping"]}]}]}"#,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Event `Jump`: unknown flag `Leaping`"));
+    }
+}
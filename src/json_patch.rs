@@ -0,0 +1,223 @@
+//! RFC 6902 JSON Patch and RFC 7386 JSON Merge Patch against the serde form of a
+//! [`ControlMap`].
+//!
+//! Patches are applied to the JSON value, and the result is only accepted if it is still a
+//! map the game reads the same way: every line must read back from `controlmap.txt` as
+//! written (hex codes, `0`/`1` remap flags, hex user event flags) and the number of contexts
+//! must not change.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{ControlMap, ControlMapError};
+//! use serde_json::json;
+//!
+//! let mut control_map = ControlMap::from_txt("// Main Gameplay\nJump\t0x39\t0xff\t0x1000\t1\t1\t1\n")?;
+//! let patch = serde_json::from_value(json!([
+//!     { "op": "replace", "path": "/lines/1/EventLine/keyboard_id/One", "value": "0x12" }
+//! ]))
+//! .unwrap();
+//! control_map.apply_json_patch(&patch)?;
+//! assert_eq!(control_map.to_string(), "// Main Gameplay\nJump\t0x12\t0xff\t0x1000\t1\t1\t1\t\n");
+//!
+//! // Not a hex code, so the map is left as it was.
+//! let patch = serde_json::from_value(json!([
+//!     { "op": "add", "path": "/lines/1/EventLine/event_binary_flag", "value": "Jumping" }
+//! ]))
+//! .unwrap();
+//! assert!(matches!(
+//!     control_map.apply_json_patch(&patch),
+//!     Err(ControlMapError::ParseError(_))
+//! ));
+//! assert_eq!(control_map.to_string(), "// Main Gameplay\nJump\t0x12\t0xff\t0x1000\t1\t1\t1\t\n");
+//! # Ok::<(), ControlMapError>(())
+//! ```
+use crate::controlmap::{ControlMap, ControlMapError, Result};
+use crate::parser::Line;
+use serde_json::{Map, Value};
+
+impl ControlMap {
+    /// Apply an RFC 6902 JSON Patch. `self` is only changed if the whole patch applies and the
+    /// result is valid.
+    ///
+    /// # Errors
+    /// - An operation fails, or the result is not the JSON form of a `ControlMap`.
+    /// - The result would not read back from `controlmap.txt` as written.
+    /// - The number of contexts would change.
+    pub fn apply_json_patch(&mut self, patch: &::json_patch::Patch) -> Result<()> {
+        let mut value = self.to_json_value()?;
+        ::json_patch::patch(&mut value, patch)
+            .map_err(|err| ControlMapError::JsonPatch(err.to_string()))?;
+        *self = self.revalidate(value)?;
+        Ok(())
+    }
+
+    /// Apply an RFC 7386 JSON Merge Patch. `self` is only changed if the result is valid.
+    ///
+    /// # Errors
+    /// Same as [`ControlMap::apply_json_patch`].
+    pub fn apply_json_merge_patch(&mut self, patch: &Value) -> Result<()> {
+        let mut value = self.to_json_value()?;
+        ::json_patch::merge(&mut value, patch);
+        *self = self.revalidate(value)?;
+        Ok(())
+    }
+
+    /// JSON Patch that turns `self` into `other`.
+    pub fn json_patch_to(&self, other: &ControlMap) -> Result<::json_patch::Patch> {
+        Ok(::json_patch::diff(
+            &self.to_json_value()?,
+            &other.to_json_value()?,
+        ))
+    }
+
+    /// JSON Merge Patch that turns `self` into `other`. Arrays such as `lines` cannot be
+    /// patched element by element in this format, so any change replaces them whole.
+    pub fn json_merge_patch_to(&self, other: &ControlMap) -> Result<Value> {
+        Ok(merge_diff(&self.to_json_value()?, &other.to_json_value()?))
+    }
+
+    fn to_json_value(&self) -> Result<Value> {
+        serde_json::to_value(self).map_err(|err| ControlMapError::JsonPatch(err.to_string()))
+    }
+
+    /// Check that `value` is a map with the contexts of `self` that reads back as written.
+    fn revalidate(&self, value: Value) -> Result<ControlMap> {
+        let patched: ControlMap = serde_json::from_value(value)
+            .map_err(|err| ControlMapError::JsonPatch(err.to_string()))?;
+
+        let (expected, found) = (self.contexts().len(), patched.contexts().len());
+        if expected != found {
+            return Err(ControlMapError::ContextCountChanged { expected, found });
+        }
+
+        let reparsed = ControlMap::from_txt(&patched.to_string())?;
+        let mismatch = (0..patched.lines.len().max(reparsed.lines.len()))
+            .find(|&idx| patched.lines.get(idx) != reparsed.lines.get(idx));
+        if let Some(idx) = mismatch {
+            let event = match patched.lines.get(idx) {
+                Some(Line::EventLine(event)) => Some(event.event_name.clone()),
+                _ => None,
+            };
+            return Err(ControlMapError::NotReadBack {
+                line: idx + 1,
+                event,
+            });
+        }
+        Ok(patched)
+    }
+}
+
+/// Merge patch from `old` to `new`: changed members of objects, `null` for removed ones, and
+/// anything else replaced whole.
+fn merge_diff(old: &Value, new: &Value) -> Value {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = Map::new();
+            for (key, old_value) in old {
+                match new.get(key) {
+                    Some(new_value) if new_value == old_value => {}
+                    Some(new_value) => {
+                        patch.insert(key.clone(), merge_diff(old_value, new_value));
+                    }
+                    None => {
+                        patch.insert(key.clone(), Value::Null);
+                    }
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    patch.insert(key.clone(), new_value.clone());
+                }
+            }
+            Value::Object(patch)
+        }
+        _ => new.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn vanilla() -> ControlMap {
+        ControlMap::from_txt(include_str!("../examples/controlmap.txt")).unwrap()
+    }
+
+    #[test]
+    fn test_generated_patches_apply() {
+        let old = vanilla();
+        let mut new = old.clone();
+        new.remove_event(crate::context::ContextKind::MenuMode, "Cancel")
+            .unwrap();
+
+        let mut patched = old.clone();
+        patched
+            .apply_json_patch(&old.json_patch_to(&new).unwrap())
+            .unwrap();
+        assert_eq!(patched, new);
+
+        let mut merged = old.clone();
+        merged
+            .apply_json_merge_patch(&old.json_merge_patch_to(&new).unwrap())
+            .unwrap();
+        assert_eq!(merged, new);
+        assert_eq!(old.json_merge_patch_to(&old).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn test_invalid_results_are_rejected() {
+        let mut control_map = vanilla();
+        let reject = |control_map: &mut ControlMap, patch: Value| {
+            let patch = serde_json::from_value(patch).unwrap();
+            let err = control_map.apply_json_patch(&patch).unwrap_err();
+            assert_eq!(*control_map, vanilla());
+            err
+        };
+
+        let err = reject(
+            &mut control_map,
+            json!([{ "op": "remove", "path": "/lines/0" }, { "op": "add", "path": "/lines/0", "value": "BlankLine" }]),
+        );
+        assert_eq!(
+            err,
+            ControlMapError::ContextCountChanged {
+                expected: 17,
+                found: 18
+            }
+        );
+
+        let err = reject(
+            &mut control_map,
+            json!([{ "op": "replace", "path": "/lines/14/EventLine/remap_key", "value": 2 }]),
+        );
+        assert!(matches!(err, ControlMapError::JsonPatch(_)));
+
+        let err = reject(
+            &mut control_map,
+            json!([{ "op": "replace", "path": "/lines/14/EventLine/event_name", "value": "Two\tFields" }]),
+        );
+        assert!(matches!(err, ControlMapError::ParseError(_)));
+
+        let err = reject(
+            &mut control_map,
+            json!([{ "op": "replace", "path": "/lines/0/Comment", "value": " Two\n// lines" }]),
+        );
+        assert_eq!(
+            err,
+            ControlMapError::NotReadBack {
+                line: 1,
+                event: None,
+            }
+        );
+        assert_eq!(err.to_string(), "Line 1 would not read back as written");
+
+        let err = reject(
+            &mut control_map,
+            json!([{ "op": "test", "path": "/lines/14/EventLine/event_name", "value": "Back" }]),
+        );
+        assert!(matches!(err, ControlMapError::JsonPatch(_)));
+    }
+}
@@ -7,7 +7,12 @@
 //!
 //! - [x] controlmap.txt => json structure
 //! - [x] json structure => controlmap.txt
-//! - [x] Analysis using enum scanCodes.
+//! - [x] Analysis using enum scanCodes, e.g. [`binding::Binding`].
+//! - [x] Lossless round trip with the concrete syntax tree in [`parser::cst`].
+//! - [x] Human friendly json with key names in [`human`] (`serde` feature).
+//! - [x] Key names and decimal codes in key fields via serde and [`ControlMap::from_txt_lenient`].
+//! - [x] `json-patch` feature: RFC 6902/7386 patches against the json structure, re-validated.
+//! - [x] `proptest` feature: `Arbitrary` generators for property testing round trips.
 //!
 //! # Examples
 //!
@@ -147,7 +152,7 @@
 //!     let formatted_control_map = r#"
 //! // Lockpicking
 //! RotatePick	0xff	0xa	0x000b	0	0	0	0x8
-//! RotateLock	0x1e+0xff	0xff	0x000c	0	0	0	0x8
+//! RotateLock	0x1e,0xff	0xff	0x000c	0	0	0	0x8
 //! DebugMode	0x35	0xff	0x4000	0	0	0	0x8
 //! Cancel	0x0f	0xff	0x1000	0	0	0	0x8
 //!
@@ -160,8 +165,32 @@
 //! }
 //! ```
 
+pub mod alias;
+#[cfg(feature = "proptest")]
+mod arbitrary;
+pub mod binding;
+pub mod conflict;
+pub mod context;
 mod controlmap;
+pub mod custom;
+pub mod diagnostic;
+pub mod diff;
+pub mod document;
+mod edit;
+pub mod free_key;
+#[cfg(feature = "serde")]
+pub mod human;
+#[cfg(feature = "json-patch")]
+pub mod json_patch;
+pub mod merge;
+mod names;
+pub mod overlay;
 pub mod parser;
+pub mod patch;
+pub mod remap;
 pub mod scan_code;
+pub mod usage;
+pub mod user_event;
+pub mod validate;
 
 pub use controlmap::{ControlMap, ControlMapError};
@@ -0,0 +1,392 @@
+//! Three-way merge of control maps.
+//!
+//! Two maps derived from the same base are merged event by event: a change made on one side
+//! only is applied, the same change made on both sides is kept once, and different changes to
+//! the same binding, remap flag or user event flag are conflicts. The merged map starts from
+//! `ours`, so its comments and layout are kept.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{merge::merge, ControlMap};
+//!
+//! let map = |jump, sneak| {
+//!     ControlMap::from_txt(&format!(
+//!         "Jump\t0x39\t0xff\t{jump}\t1\t1\t1\t0xc01\nSneak\t{sneak}\t0xff\t0x0080\t1\t1\t1\t0x881\n"
+//!     ))
+//! };
+//! let base = map("0x1000", "0x1d")?;
+//! let ours = map("0x2000", "0x1d")?;
+//! let theirs = map("0x4000", "0x2e")?;
+//!
+//! let merge = merge(&base, &ours, &theirs);
+//! assert_eq!(merge.conflicts.len(), 1);
+//! assert_eq!(
+//!     merge.annotated().to_string(),
+//!     "\
+//! // <<< merge conflict: Jump gamepad: base 0x1000, ours 0x2000, theirs 0x4000
+//! Jump\t0x39\t0xff\t0x2000\t1\t1\t1\t0xc01
+//! Sneak\t0x2e\t0xff\t0x0080\t1\t1\t1\t0x881
+//! "
+//! );
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::ControlMap;
+use crate::parser::{EventLine, Line};
+use crate::scan_code::Device;
+use crate::user_event::UserEventFlag;
+use core::fmt;
+
+/// Part of an event two sides changed differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MergeField {
+    Binding(Device),
+    Remap(Device),
+    Flag,
+    /// The event was added, or removed on one side and changed on the other.
+    Event,
+}
+
+impl fmt::Display for MergeField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Binding(device) => write!(f, "{}", device),
+            Self::Remap(device) => write!(f, "{} remap", device),
+            Self::Flag => write!(f, "flag"),
+            Self::Event => write!(f, "event"),
+        }
+    }
+}
+
+/// A change that could not be merged. Values are as written in `controlmap.txt`, `None` if the
+/// event does not exist on that side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MergeConflict {
+    pub context: ContextKind,
+    pub event: String,
+    pub field: MergeField,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "none".into());
+        write!(
+            f,
+            "{} {}: base {}, ours {}, theirs {}",
+            self.event,
+            self.field,
+            value(&self.base),
+            value(&self.ours),
+            value(&self.theirs)
+        )
+    }
+}
+
+/// Result of [`merge`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merge {
+    /// `ours` with every change of `theirs` that did not conflict. Conflicts keep `ours`.
+    pub merged: ControlMap,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl Merge {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    /// The merged map with a `// <<< merge conflict: ...` comment above each conflicting event,
+    /// at the end of its context if the event is gone, or at the end of the file if the context
+    /// is gone too.
+    pub fn annotated(&self) -> ControlMap {
+        let mut comments: Vec<(usize, Line)> = Vec::new();
+        for conflict in &self.conflicts {
+            let position = match self.merged.context(conflict.context) {
+                Some(context) => self
+                    .merged
+                    .find_event(conflict.context, &conflict.event)
+                    .unwrap_or(context.lines.end),
+                None => self.merged.iter().len(),
+            };
+            let comment = format!(" <<< merge conflict: {}", conflict);
+            comments.push((position, Line::Comment(comment)));
+        }
+
+        // Insert from the bottom so earlier positions stay valid.
+        comments.sort_by_key(|(position, _)| *position);
+        let mut lines: Vec<Line> = self.merged.iter().cloned().collect();
+        for (position, comment) in comments.into_iter().rev() {
+            lines.insert(position, comment);
+        }
+        ControlMap::from_lines(lines)
+    }
+}
+
+/// Merge the changes `ours` and `theirs` made to `base`.
+pub fn merge(base: &ControlMap, ours: &ControlMap, theirs: &ControlMap) -> Merge {
+    let mut merged = ours.clone();
+    let mut conflicts = Vec::new();
+    let contexts = base
+        .contexts()
+        .len()
+        .max(ours.contexts().len())
+        .max(theirs.contexts().len());
+
+    for index in 0..contexts {
+        let context = ContextKind::from_index(index);
+        let events = |map: &ControlMap| -> Vec<EventLine> {
+            map.context(context)
+                .map(|c| c.events.into_iter().cloned().collect())
+                .unwrap_or_default()
+        };
+        let (base_events, our_events, their_events) = (events(base), events(ours), events(theirs));
+        let find = |events: &[EventLine], name: &str| {
+            events
+                .iter()
+                .find(|event| event.event_name == name)
+                .cloned()
+        };
+
+        let mut names: Vec<&str> = Vec::new();
+        for event in base_events.iter().chain(&our_events).chain(&their_events) {
+            if !names.contains(&event.event_name.as_str()) {
+                names.push(&event.event_name);
+            }
+        }
+
+        for name in names {
+            let base_event = find(&base_events, name);
+            let our_event = find(&our_events, name);
+            let their_event = find(&their_events, name);
+            let mut conflict = |field, base: Option<String>, ours: Option<String>, theirs| {
+                conflicts.push(MergeConflict {
+                    context,
+                    event: name.into(),
+                    field,
+                    base,
+                    ours,
+                    theirs,
+                })
+            };
+            let line = |event: &Option<EventLine>| event.as_ref().map(ToString::to_string);
+
+            match (&base_event, &our_event, &their_event) {
+                (Some(base), Some(ours), Some(theirs)) => {
+                    let (event, fields) = merge_event(base, ours, theirs);
+                    for (field, values) in fields {
+                        let [base, ours, theirs] = values.map(Some);
+                        conflict(field, base, ours, theirs);
+                    }
+                    if let Ok(position) = merged.find_event(context, name) {
+                        merged.lines[position] = Line::EventLine(event);
+                    }
+                }
+                // Removed by theirs.
+                (Some(base), Some(ours), None) if same_event(base, ours) => {
+                    let _ = merged.remove_event(context, name);
+                }
+                // Removed by ours, or by both.
+                (Some(base), None, Some(theirs)) if same_event(base, theirs) => {}
+                (Some(_), None, None) => {}
+                (None, Some(ours), Some(theirs)) if same_event(ours, theirs) => {}
+                (None, Some(_), None) => {}
+                // Added by theirs.
+                (None, None, Some(theirs)) => {
+                    let after = their_events
+                        .iter()
+                        .take_while(|event| event.event_name != name)
+                        .filter_map(|event| {
+                            let merged_context = merged.context(context)?;
+                            let events = merged_context.events;
+                            events.iter().position(|e| e.event_name == event.event_name)
+                        })
+                        .last();
+                    let index = after.map_or(0, |idx| idx + 1);
+                    if merged
+                        .insert_event_at(context, index, theirs.clone())
+                        .is_err()
+                    {
+                        conflict(MergeField::Event, None, None, line(&their_event));
+                    }
+                }
+                _ => conflict(
+                    MergeField::Event,
+                    line(&base_event),
+                    line(&our_event),
+                    line(&their_event),
+                ),
+            }
+        }
+    }
+
+    Merge { merged, conflicts }
+}
+
+/// Merge the fields of one event, returning the conflicting ones with their three values.
+fn merge_event(
+    base: &EventLine,
+    ours: &EventLine,
+    theirs: &EventLine,
+) -> (EventLine, Vec<(MergeField, [String; 3])>) {
+    let mut merged = ours.clone();
+    let mut conflicts = Vec::new();
+
+    let sides = [base, ours, theirs];
+    for device in Device::ALL {
+        let values = sides.map(|event| event.key_id(device));
+        match pick(&values) {
+            Some(side) => *merged.key_id_mut(device) = values[side].clone(),
+            None => conflicts.push((MergeField::Binding(device), values.map(ToString::to_string))),
+        }
+    }
+    for device in Device::ALL {
+        let values = sides.map(|event| event.remappable(device));
+        match pick(&values) {
+            Some(side) => match device {
+                Device::Keyboard => merged.remap_key = values[side],
+                Device::Mouse => merged.remap_mouse = values[side],
+                Device::Gamepad => merged.remap_gamepad = values[side],
+            },
+            None => conflicts.push((
+                MergeField::Remap(device),
+                values.map(|remap| (remap as u8).to_string()),
+            )),
+        }
+    }
+    let flags = sides.map(decoded_flag);
+    match pick(&flags) {
+        Some(side) => merged.event_binary_flag = sides[side].event_binary_flag.clone(),
+        None => conflicts.push((
+            MergeField::Flag,
+            sides.map(|event| {
+                event
+                    .event_binary_flag
+                    .clone()
+                    .unwrap_or_else(|| "none".into())
+            }),
+        )),
+    }
+
+    (merged, conflicts)
+}
+
+/// Whether `a` and `b` are the same event, comparing fields the way [`merge_event`] does.
+fn same_event(a: &EventLine, b: &EventLine) -> bool {
+    a.event_name == b.event_name
+        && Device::ALL.into_iter().all(|device| {
+            a.key_id(device) == b.key_id(device) && a.remappable(device) == b.remappable(device)
+        })
+        && decoded_flag(a) == decoded_flag(b)
+}
+
+/// Flag of `event` decoded so that `0x8` and `0x0008` are the same, or the column as written if
+/// it cannot be decoded.
+fn decoded_flag(event: &EventLine) -> Result<Option<UserEventFlag>, Option<String>> {
+    event
+        .user_event_flag()
+        .map_err(|_| event.event_binary_flag.clone())
+}
+
+/// Side of `[base, ours, theirs]` whose value wins a three-way merge, `None` on conflict.
+fn pick<T: PartialEq>([base, ours, theirs]: &[T; 3]) -> Option<usize> {
+    if ours == theirs || theirs == base {
+        Some(1)
+    } else if ours == base {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_merge_events() {
+        let base = ControlMap::from_txt(
+            "// Main Gameplay\nA\t0x1\t0xff\t0xff\t1\t1\t1\nB\t0x2\t0xff\t0xff\t1\t1\t1\nC\t0x3\t0xff\t0xff\t1\t1\t1\n",
+        )
+        .unwrap();
+        let ours = ControlMap::from_txt(
+            "// Main Gameplay\nA\t0x1\t0xff\t0xff\t1\t1\t1\t0x8\nB\t0x2\t0xff\t0xff\t1\t1\t1\n",
+        )
+        .unwrap();
+        let theirs = ControlMap::from_txt(
+            "// Main Gameplay\nA\t0x1\t0xff\t0xff\t0\t1\t1\nD\t0x4\t0xff\t0xff\t1\t1\t1\nB\t0x2\t0xff\t0xff\t1\t1\t1\nC\t0x3\t0x1\t0xff\t1\t1\t1\n",
+        )
+        .unwrap();
+
+        let merge = merge(&base, &ours, &theirs);
+        assert_eq!(
+            merge.conflicts,
+            [MergeConflict {
+                context: ContextKind::MainGameplay,
+                event: "C".into(),
+                field: MergeField::Event,
+                base: Some("C\t0x3\t0xff\t0xff\t1\t1\t1\t".into()),
+                ours: None,
+                theirs: Some("C\t0x3\t0x1\t0xff\t1\t1\t1\t".into()),
+            }]
+        );
+        assert_eq!(
+            merge.merged,
+            ControlMap::from_txt(
+                "// Main Gameplay\nA\t0x1\t0xff\t0xff\t0\t1\t1\t0x8\nD\t0x4\t0xff\t0xff\t1\t1\t1\nB\t0x2\t0xff\t0xff\t1\t1\t1\n",
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            merge.annotated().iter().last(),
+            Some(&Line::Comment(
+                " <<< merge conflict: C event: base C\t0x3\t0xff\t0xff\t1\t1\t1\t, ours none, theirs C\t0x3\t0x1\t0xff\t1\t1\t1\t".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_remove_ignores_flag_spelling() {
+        let base = ControlMap::from_txt("A\t0x1\t0xff\t0xff\t1\t1\t1\t0x8\n").unwrap();
+        let ours = ControlMap::from_txt("A\t0x0001\t0xff\t0xff\t1\t1\t1\t0x0008\n").unwrap();
+        let theirs = ControlMap::from_txt("// emptied\n").unwrap();
+
+        let removed_by_theirs = merge(&base, &ours, &theirs);
+        assert!(removed_by_theirs.is_clean());
+        assert!(removed_by_theirs
+            .merged
+            .context(ContextKind::MainGameplay)
+            .unwrap()
+            .events
+            .is_empty());
+
+        assert!(merge(&base, &theirs, &ours).is_clean());
+    }
+
+    #[test]
+    fn test_annotated_keeps_conflicts_without_context() {
+        let merged = ControlMap::from_txt("A\t0x1\t0xff\t0xff\t1\t1\t1\n").unwrap();
+        let conflict = MergeConflict {
+            context: ContextKind::MenuMode,
+            event: "B".into(),
+            field: MergeField::Event,
+            base: None,
+            ours: None,
+            theirs: Some("B".into()),
+        };
+        let merge = Merge {
+            merged,
+            conflicts: vec![conflict.clone()],
+        };
+        assert_eq!(
+            merge.annotated().iter().last(),
+            Some(&Line::Comment(format!(" <<< merge conflict: {conflict}")))
+        );
+    }
+}
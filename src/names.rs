@@ -0,0 +1,387 @@
+//! Names of devices, keys and user event flags in the human oriented formats.
+use crate::parser::{KeyCode, KeyID};
+use crate::scan_code::Device;
+use crate::user_event::UserEventFlag;
+use core::fmt;
+use core::ops::Range;
+use nom::{
+    bytes::complete::take_while1,
+    character::complete::{char, space0},
+    combinator::{all_consuming, map, map_opt},
+    multi::separated_list1,
+    sequence::{preceded, terminated},
+};
+
+type IResult<'a, O> = nom::IResult<&'a str, O>;
+
+pub(crate) fn device_from_name(name: &str) -> Option<Device> {
+    Device::ALL
+        .into_iter()
+        .find(|device| device.to_string().eq_ignore_ascii_case(name))
+}
+
+/// Keys such as `LShift+_1, 0x2a`, with names looked up on `device`.
+pub(crate) fn parse_key_id(device: Device, input: &str) -> Option<KeyID> {
+    if let Some(alias) = input.strip_prefix("!0,") {
+        return Some(KeyID::Alias(alias.into()));
+    }
+
+    let key = key_code(device);
+    let chord = map(
+        separated_list1(preceded(space0, char('+')), key),
+        |mut keys| match keys.len() {
+            1 => KeyID::One(keys.remove(0)),
+            _ => KeyID::And(keys.into_iter().map(KeyID::One).collect()),
+        },
+    );
+    let mut alternatives = all_consuming(terminated(
+        map(
+            separated_list1(preceded(space0, char(',')), chord),
+            |mut keys| match keys.len() {
+                1 => keys.remove(0),
+                _ => KeyID::Or(keys),
+            },
+        ),
+        space0,
+    ));
+    alternatives(input).ok().map(|(_, key_id)| key_id)
+}
+
+/// A key of `device` as in [`key_code_from_str`].
+fn key_code<'a>(device: Device) -> impl FnMut(&'a str) -> IResult<'a, KeyCode> {
+    let word = take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_');
+    map_opt(preceded(space0, word), move |word: &str| {
+        key_code_from_str(device, word)
+    })
+}
+
+/// A hex code as written, or a decimal code or key name of `device` in canonical hex.
+pub(crate) fn key_code_from_str(device: Device, word: &str) -> Option<KeyCode> {
+    if word.starts_with("0x") || word.starts_with("0X") {
+        return word.parse().ok();
+    }
+    if !word.is_empty() && word.bytes().all(|b| b.is_ascii_digit()) {
+        return word.parse().ok().map(KeyCode::new);
+    }
+    device.code_from_name(word).map(KeyCode::new)
+}
+
+/// `controlmap.txt` with key names and decimal codes in the key id fields replaced by hex, and
+/// `0X` prefixes by `0x`.
+/// Fields that cannot be read are left for the parser to report.
+pub(crate) struct NormalizedKeys {
+    pub text: String,
+    /// Byte range of every replaced field in `text` and in the original input.
+    replaced: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl NormalizedKeys {
+    pub fn new(txt: &str) -> Self {
+        let mut text = String::with_capacity(txt.len());
+        let mut replaced = Vec::new();
+        let mut line_start = 0;
+        for line in txt.split_inclusive('\n') {
+            let mut copied = 0;
+            for (range, hex) in key_field_replacements(line) {
+                text += &line[copied..range.start];
+                let start = text.len();
+                text += &hex;
+                replaced.push((
+                    start..text.len(),
+                    line_start + range.start..line_start + range.end,
+                ));
+                copied = range.end;
+            }
+            text += &line[copied..];
+            line_start += line.len();
+        }
+        Self { text, replaced }
+    }
+
+    /// Byte offset in the original input of `offset` in `text`. Offsets inside a replaced
+    /// field point at its start.
+    pub fn original_offset(&self, offset: usize) -> usize {
+        match self
+            .replaced
+            .iter()
+            .rev()
+            .find(|(normalized, _)| normalized.start <= offset)
+        {
+            Some((normalized, original)) if offset < normalized.end => original.start,
+            Some((normalized, original)) => offset - normalized.end + original.end,
+            None => offset,
+        }
+    }
+}
+
+/// Key names, decimal codes and `0X` prefixes in the key id fields of `line`, with their byte
+/// range in `line` and their hex form. Hex codes, spaces and separators are kept as written.
+fn key_field_replacements(line: &str) -> Vec<(Range<usize>, String)> {
+    let content = line.trim_end_matches(['\r', '\n']);
+    if content.trim().is_empty() || content.trim_start().starts_with("//") {
+        return Vec::new();
+    }
+
+    let mut replacements = Vec::new();
+    let mut nth: usize = 0;
+    let mut start = 0;
+    for field in content.split('\t') {
+        let range = start..start + field.len();
+        start = range.end + 1;
+        if field.is_empty() {
+            continue;
+        }
+        let device = nth.checked_sub(1).and_then(|idx| Device::ALL.get(idx));
+        nth += 1;
+        let Some(&device) = device else {
+            continue;
+        };
+        if matches!(parse_key_id(device, field), None | Some(KeyID::Alias(_))) {
+            continue;
+        }
+
+        let mut word_start = range.start;
+        for word in field.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_')) {
+            let word_range = word_start..word_start + word.len();
+            word_start = word_range.end + 1;
+            let hex = match word.strip_prefix("0X") {
+                _ if word.is_empty() || word.starts_with("0x") => continue,
+                Some(digits) => format!("0x{digits}"),
+                None => match key_code_from_str(device, word) {
+                    Some(code) => code.to_string(),
+                    None => continue,
+                },
+            };
+            replacements.push((word_range, hex));
+        }
+    }
+    replacements
+}
+
+/// Deserializers of the key id columns that also take key names and decimal codes, either in
+/// the tagged form (`{"One": "LShift"}`, `{"One": 42}`) or as a bare string such as
+/// `"LShift+_1"` or number.
+#[cfg(feature = "serde")]
+pub(crate) mod lenient {
+    use super::{key_code_from_str, parse_key_id};
+    use crate::parser::{KeyCode, KeyID};
+    use crate::scan_code::Device;
+    use serde::{de::Error as _, Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Lenient {
+        Tagged(Tagged),
+        Text(String),
+        Number(u32),
+    }
+
+    #[derive(Deserialize)]
+    enum Tagged {
+        Or(Vec<Lenient>),
+        And(Vec<Lenient>),
+        One(Key),
+        Alias(String),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Key {
+        Text(String),
+        Number(u32),
+    }
+
+    pub(crate) fn keyboard<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyID, D::Error> {
+        key_id(Device::Keyboard, deserializer)
+    }
+
+    pub(crate) fn mouse<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyID, D::Error> {
+        key_id(Device::Mouse, deserializer)
+    }
+
+    pub(crate) fn gamepad<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyID, D::Error> {
+        key_id(Device::Gamepad, deserializer)
+    }
+
+    fn key_id<'de, D: Deserializer<'de>>(
+        device: Device,
+        deserializer: D,
+    ) -> Result<KeyID, D::Error> {
+        convert(device, Lenient::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+
+    fn convert(device: Device, lenient: Lenient) -> Result<KeyID, String> {
+        let unknown = |keys: &str| format!("unknown {} key `{}`", device, keys);
+        let all = |keys: Vec<Lenient>| {
+            keys.into_iter()
+                .map(|key| convert(device, key))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        match lenient {
+            Lenient::Tagged(Tagged::Or(keys)) => all(keys).map(KeyID::Or),
+            Lenient::Tagged(Tagged::And(keys)) => all(keys).map(KeyID::And),
+            Lenient::Tagged(Tagged::One(Key::Text(key))) => key_code_from_str(device, &key)
+                .map(KeyID::One)
+                .ok_or_else(|| unknown(&key)),
+            Lenient::Tagged(Tagged::One(Key::Number(code))) | Lenient::Number(code) => {
+                Ok(KeyID::One(KeyCode::new(code)))
+            }
+            Lenient::Tagged(Tagged::Alias(alias)) => Ok(KeyID::Alias(alias)),
+            Lenient::Text(keys) => parse_key_id(device, &keys).ok_or_else(|| unknown(&keys)),
+        }
+    }
+}
+
+/// `Movement | Jumping`, `main_four` or `0xc01`.
+pub(crate) fn parse_flag(input: &str) -> Option<UserEventFlag> {
+    if input.starts_with("0x") || input.starts_with("0X") {
+        return input.parse().ok();
+    }
+    input
+        .split('|')
+        .try_fold(UserEventFlag::empty(), |flags, name| {
+            let name = name.trim().replace('_', "");
+            let (_, flag) = UserEventFlag::all()
+                .iter_names()
+                .find(|(known, _)| known.replace('_', "").eq_ignore_ascii_case(&name))?;
+            Some(flags | flag)
+        })
+}
+
+/// Names of the groups in `flag`, e.g. `MainFour`, or `None` if it has unknown bits.
+pub(crate) fn flag_names(flag: UserEventFlag) -> Option<Vec<String>> {
+    let mut known = UserEventFlag::empty();
+    let mut names = Vec::new();
+    for (name, named) in flag.iter_names() {
+        known |= named;
+        names.push(
+            name.split('_')
+                .map(|word| word[..1].to_string() + &word[1..].to_lowercase())
+                .collect(),
+        );
+    }
+    (known == flag).then_some(names)
+}
+
+/// Names of the groups in `flag` joined by `|`, or hex if it has unknown bits or none at all.
+pub(crate) fn flag_text(flag: UserEventFlag) -> String {
+    match flag_names(flag) {
+        Some(names) if !names.is_empty() => names.join(" | "),
+        _ => flag.to_string(),
+    }
+}
+
+/// (De)serialize a flag as its group names, e.g. `["Movement", "Jumping"]`, or as hex if it has
+/// unknown bits.
+#[cfg(feature = "serde")]
+pub(crate) mod flag_groups {
+    use super::{flag_names, parse_flag};
+    use crate::user_event::UserEventFlag;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Groups {
+        Names(Vec<String>),
+        Raw(String),
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        flag: &UserEventFlag,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match flag_names(*flag) {
+            Some(names) => Groups::Names(names),
+            None => Groups::Raw(flag.to_string()),
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<UserEventFlag, D::Error> {
+        let unknown = |name: &str| serde::de::Error::custom(format!("unknown flag `{name}`"));
+        match Groups::deserialize(deserializer)? {
+            Groups::Raw(raw) => raw.parse().map_err(serde::de::Error::custom),
+            Groups::Names(names) => names.iter().try_fold(UserEventFlag::empty(), |flag, name| {
+                Ok(flag | parse_flag(name).ok_or_else(|| unknown(name))?)
+            }),
+        }
+    }
+}
+
+/// Keys written with their names on `device` where they have one.
+pub(crate) struct NamedKeys<'a>(pub Device, pub &'a KeyID);
+
+impl fmt::Display for NamedKeys<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let NamedKeys(device, key_id) = *self;
+        let join = |f: &mut fmt::Formatter<'_>, keys: &[KeyID], separator| {
+            for (idx, key) in keys.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, "{}", separator)?;
+                }
+                write!(f, "{}", NamedKeys(device, key))?;
+            }
+            Ok(())
+        };
+        match key_id {
+            KeyID::Or(keys) => join(f, keys, ", "),
+            KeyID::And(keys) => join(f, keys, "+"),
+            KeyID::One(key) if key.as_str() != key.canonical().as_str() => write!(f, "{}", key),
+            KeyID::One(key) => match device.code_name(key.code()) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "{}", key),
+            },
+            KeyID::Alias(alias) => write!(f, "!0,{}", alias),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_normalized_keys() {
+        let input = "// Jump\tSpace\nJump\tSpace\t0xff\t_360_A,16\t1\t1\t1\r\nBad\tSpacebar\t0xff\t0x1000\t1\t1\t1\nHex\t 0x11\t0xff\t0X1E+A\t1\t1\t1\n";
+        let normalized = NormalizedKeys::new(input);
+        assert_eq!(
+            normalized.text,
+            "// Jump\tSpace\nJump\t0x39\t0xff\t0x1000,0x10\t1\t1\t1\r\nBad\tSpacebar\t0xff\t0x1000\t1\t1\t1\nHex\t 0x11\t0xff\t0x1E+0x1000\t1\t1\t1\n"
+        );
+
+        let offset = |text: &str, needle: &str| text.find(needle).unwrap();
+        let from = |needle| normalized.original_offset(offset(&normalized.text, needle));
+        assert_eq!(from("0x39"), offset(input, "Space\t0xff"));
+        assert_eq!(from("0x1000,"), offset(input, "_360_A"));
+        assert_eq!(from("Spacebar"), offset(input, "Spacebar"));
+        assert_eq!(from("0x1E"), offset(input, "0X1E"));
+        assert_eq!(from(" 0x11"), offset(input, " 0x11"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_lenient_deserialize() {
+        use crate::parser::EventLine;
+
+        let event: EventLine = serde_json::from_str(
+            r#"{"event_name": "Sprint", "keyboard_id": "LAlt", "mouse_id": {"One": 1},
+                "gamepad_id": {"Or": [{"And": ["LB", {"One": "AButton"}]}, 9]},
+                "remap_key": true, "remap_mouse": true, "remap_gamepad": false}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            event.to_string(),
+            "Sprint\t0x38\t0x1\t0x100+0x1000,0x9\t1\t1\t0\t"
+        );
+
+        let err = serde_json::from_str::<EventLine>(
+            r#"{"event_name": "Sprint", "keyboard_id": "Spacebar", "mouse_id": "0xff",
+                "gamepad_id": "0xff", "remap_key": true, "remap_mouse": true, "remap_gamepad": false}"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown keyboard key `Spacebar`"));
+    }
+}
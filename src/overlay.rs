@@ -0,0 +1,318 @@
+//! Layered control maps with provenance.
+//!
+//! Layers are applied in order, the way a mod manager resolves files: vanilla first, then each
+//! mod, then the player's overrides. An event of a later layer replaces the event of the same
+//! name in the same context, events only found in a later layer are appended to their
+//! context, and events a layer does not mention are left as they are. A layer cannot add
+//! contexts, since that would shift the context layout the game reads.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{
+//!     context::ContextKind,
+//!     overlay::{Field, OverlayStack},
+//!     scan_code::Device,
+//!     ControlMap,
+//! };
+//!
+//! let vanilla = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+//! let sprint_mod = ControlMap::from_txt("Sprint\t0x38\t0xff\t0x0040\t1\t1\t1\t0x801\n")?;
+//! let no_mouse = ControlMap::from_txt("Sprint\t0x38\t0xff\t0x0040\t1\t0\t1\t0x801\n")?;
+//!
+//! let mut stack = OverlayStack::new();
+//! stack.push("vanilla", vanilla);
+//! stack.push("Sprint on Alt", sprint_mod);
+//! stack.push("No mouse remap", no_mouse);
+//! let overlay = stack.resolve()?;
+//!
+//! let source = |field| overlay.field_source(ContextKind::MainGameplay, "Sprint", field);
+//! assert_eq!(source(Field::Binding(Device::Keyboard)), Some("Sprint on Alt"));
+//! assert_eq!(source(Field::Binding(Device::Gamepad)), Some("vanilla"));
+//! assert_eq!(source(Field::Remappable(Device::Mouse)), Some("No mouse remap"));
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::{ControlMap, ControlMapError, Result};
+use crate::parser::{EventLine, Line};
+use crate::scan_code::Device;
+
+/// Named control maps, lowest priority first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OverlayStack {
+    layers: Vec<(String, ControlMap)>,
+}
+
+/// Part of an event line a layer can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Field {
+    Binding(Device),
+    Remappable(Device),
+    /// The user event flag column.
+    Flag,
+}
+
+impl Field {
+    /// Every field of an event line.
+    pub const ALL: [Self; 7] = [
+        Self::Binding(Device::Keyboard),
+        Self::Binding(Device::Mouse),
+        Self::Binding(Device::Gamepad),
+        Self::Remappable(Device::Keyboard),
+        Self::Remappable(Device::Mouse),
+        Self::Remappable(Device::Gamepad),
+        Self::Flag,
+    ];
+
+    fn differs(&self, a: &EventLine, b: &EventLine) -> bool {
+        match *self {
+            Self::Binding(device) => a.key_id(device) != b.key_id(device),
+            Self::Remappable(device) => a.remappable(device) != b.remappable(device),
+            Self::Flag => a.event_binary_flag != b.event_binary_flag,
+        }
+    }
+}
+
+/// Layer that supplied the current value of one field of an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Provenance {
+    pub context: ContextKind,
+    pub event: String,
+    pub field: Field,
+    pub layer: String,
+}
+
+/// Result of [`OverlayStack::resolve`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlay {
+    /// The effective map.
+    pub control_map: ControlMap,
+    /// One entry per event and field.
+    pub provenance: Vec<Provenance>,
+}
+
+impl Overlay {
+    /// Name of the layer that last changed the `device` binding of `event`.
+    pub fn source(&self, context: ContextKind, event: &str, device: Device) -> Option<&str> {
+        self.field_source(context, event, Field::Binding(device))
+    }
+
+    /// Name of the layer that last changed `field` of `event`.
+    pub fn field_source(&self, context: ContextKind, event: &str, field: Field) -> Option<&str> {
+        self.provenance
+            .iter()
+            .find(|p| p.context == context && p.event == event && p.field == field)
+            .map(|p| p.layer.as_str())
+    }
+}
+
+impl OverlayStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a layer above the existing ones.
+    pub fn push(&mut self, name: impl Into<String>, control_map: ControlMap) {
+        self.layers.push((name.into(), control_map));
+    }
+
+    pub fn layers(&self) -> &[(String, ControlMap)] {
+        &self.layers
+    }
+
+    /// Apply every layer in order. The first layer is taken as is, comments included.
+    ///
+    /// # Errors
+    /// A later layer has events in a context the first layer does not have.
+    pub fn resolve(&self) -> Result<Overlay> {
+        let mut layers = self.layers.iter();
+        let Some((name, base)) = layers.next() else {
+            return Ok(Overlay {
+                control_map: ControlMap::from_lines(Vec::new()),
+                provenance: Vec::new(),
+            });
+        };
+
+        let mut control_map = base.clone();
+        let mut provenance = Vec::new();
+        for context in base.contexts() {
+            for event in context.events {
+                record(
+                    &mut provenance,
+                    context.kind,
+                    &event.event_name,
+                    &Field::ALL,
+                    name,
+                );
+            }
+        }
+
+        for (name, layer) in layers {
+            for (context, event, fields) in control_map.apply_overlay(layer)? {
+                record(&mut provenance, context, &event, &fields, name);
+            }
+        }
+
+        Ok(Overlay {
+            control_map,
+            provenance,
+        })
+    }
+}
+
+fn record(
+    provenance: &mut Vec<Provenance>,
+    context: ContextKind,
+    event: &str,
+    fields: &[Field],
+    layer: &str,
+) {
+    for &field in fields {
+        let entry = provenance
+            .iter_mut()
+            .find(|p| p.context == context && p.event == event && p.field == field);
+        match entry {
+            Some(entry) => entry.layer = layer.into(),
+            None => provenance.push(Provenance {
+                context,
+                event: event.into(),
+                field,
+                layer: layer.into(),
+            }),
+        }
+    }
+}
+
+impl ControlMap {
+    /// Replace or add the events of `layer`, context by context.
+    ///
+    /// Returns each event that was added or changed, with the fields that changed (all of them
+    /// for added events).
+    ///
+    /// # Errors
+    /// `layer` has events in a context `self` does not have. `self` is left unchanged.
+    pub(crate) fn apply_overlay(
+        &mut self,
+        layer: &ControlMap,
+    ) -> Result<Vec<(ContextKind, String, Vec<Field>)>> {
+        let contexts: Vec<_> = layer
+            .contexts()
+            .into_iter()
+            .filter(|context| !context.events.is_empty())
+            .collect();
+        if let Some(missing) = contexts
+            .iter()
+            .find(|context| self.context(context.kind).is_none())
+        {
+            return Err(ControlMapError::ContextNotFound(missing.kind));
+        }
+
+        let mut changes = Vec::new();
+        for context in contexts {
+            for &event in &context.events {
+                let name = event.event_name.as_str();
+                match self.find_event(context.kind, name) {
+                    Ok(position) => {
+                        let Line::EventLine(current) = &mut self.lines[position] else {
+                            unreachable!("find_event only returns event lines");
+                        };
+                        let fields: Vec<_> = Field::ALL
+                            .into_iter()
+                            .filter(|field| field.differs(current, event))
+                            .collect();
+                        if current != event {
+                            *current = event.clone();
+                            changes.push((context.kind, name.into(), fields));
+                        }
+                    }
+                    Err(_) => {
+                        self.append_event(context.kind, event.clone());
+                        changes.push((context.kind, name.into(), Field::ALL.to_vec()));
+                    }
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Append `event` after the last line of `context` without the checks of `insert_event`.
+    fn append_event(&mut self, context: ContextKind, event: EventLine) {
+        if let Some(context) = self.context(context) {
+            let end = context.lines.end;
+            self.lines.insert(end, Line::EventLine(event));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_layers() {
+        let mut stack = OverlayStack::new();
+        stack.push(
+            "base",
+            ControlMap::from_txt(
+                "// Main Gameplay\nJump\t0x39\t0xff\t0x1000\t1\t1\t1\t0xc01\n\n\n// Console\n",
+            )
+            .unwrap(),
+        );
+        stack.push(
+            "mod",
+            ControlMap::from_txt(
+                "Jump\t0x39\t0x1\t0x1000\t1\t1\t1\t0xc01\n\n\nDodge\t0x38\t0xff\t0xff\t1\t1\t1\n",
+            )
+            .unwrap(),
+        );
+        stack.push(
+            "flags",
+            ControlMap::from_txt("Jump\t0x39\t0x1\t0x1000\t1\t0\t1\t0x401\n").unwrap(),
+        );
+        let overlay = stack.resolve().unwrap();
+
+        assert_eq!(
+            overlay.control_map,
+            ControlMap::from_txt(
+                "// Main Gameplay\nJump\t0x39\t0x1\t0x1000\t1\t0\t1\t0x401\n\n\n// Console\nDodge\t0x38\t0xff\t0xff\t1\t1\t1\n"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            overlay.source(ContextKind::MainGameplay, "Jump", Device::Mouse),
+            Some("mod")
+        );
+        assert_eq!(
+            overlay.source(ContextKind::MainGameplay, "Jump", Device::Keyboard),
+            Some("base")
+        );
+        assert_eq!(
+            overlay.source(ContextKind::Console, "Dodge", Device::Gamepad),
+            Some("mod")
+        );
+        let source = |field| overlay.field_source(ContextKind::MainGameplay, "Jump", field);
+        assert_eq!(source(Field::Remappable(Device::Mouse)), Some("flags"));
+        assert_eq!(source(Field::Remappable(Device::Keyboard)), Some("base"));
+        assert_eq!(source(Field::Flag), Some("flags"));
+    }
+
+    #[test]
+    fn test_missing_context() {
+        let mut stack = OverlayStack::new();
+        stack.push(
+            "base",
+            ControlMap::from_txt("Jump\t0x39\t0xff\t0x1000\t1\t1\t1\n").unwrap(),
+        );
+        stack.push(
+            "mod",
+            ControlMap::from_txt("\n// Menu Mode\nAccept\t0x1c\t0xff\t0x1000\t0\t0\t0\n").unwrap(),
+        );
+        assert_eq!(
+            stack.resolve(),
+            Err(ControlMapError::ContextNotFound(ContextKind::MenuMode))
+        );
+    }
+}
@@ -10,7 +10,7 @@
 //!          | <event-line>
 //!          | <blank-line>
 //!
-//! <comment-line> ::= "//" <string>
+//! <comment-line> ::= "//" <string> <new-line>
 //!
 //! <event-line> ::= <event-name> "\t"+ <keyboard-id> "\t"+ <mouse-id> "\t"+ <gamepad-id> "\t"+ <remap-key> "\t"+ <remap-mouse> "\t"+ <remap-gamepad> "\t"+ [<event-binary-flag>] <new-line>
 //!
@@ -30,7 +30,7 @@
 //!
 //! <event-binary-flag> ::= <hexadecimal>
 //!
-//! <blank-line> ::= <new-line>
+//! <blank-line> ::= (" " | "\t")* <new-line>
 //!
 //! # KeyMap
 //! <key-map> ::= <key-alias> | <key-or> | <key-and> | <hexadecimal>
@@ -41,7 +41,7 @@
 //!
 //! # primitives
 //!
-//! <new-line> ::= "\r"? "\n"
+//! <new-line> ::= "\r"? "\n" | EOF
 //! <string> ::= Any valid string
 //!
 //! <hexadecimal> ::= "0x" (<hex-digit>)+
@@ -50,15 +50,20 @@
 //!                   "a" | "b" | "c" | "d" | "e" | "f" | "A" | "B" | "C" | "D" | "E" | "F"
 //! ```
 
-use core::fmt;
+pub mod cst;
+
+use crate::diagnostic::{Diagnostic, DiagnosticCode};
+use crate::scan_code::{Device, ScanCodeError};
+use crate::user_event::UserEventFlag;
+use core::{fmt, str::FromStr};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while1},
-    character::complete::{char, hex_digit1, line_ending, multispace1, not_line_ending, space0},
-    combinator::{map, opt, recognize},
+    character::complete::{char, hex_digit1, line_ending, not_line_ending, one_of, space0, space1},
+    combinator::{consumed, eof, map, map_res, opt, peek, recognize},
     error::{context, ErrorKind, ParseError},
     multi::{many0, many1},
-    sequence::preceded,
+    sequence::{preceded, terminated},
     AsChar, InputTakeAtPosition,
 };
 
@@ -67,7 +72,7 @@ use nom::{
 // but the memory it owns is dropped during the deserialization process.
 
 /// KeyMap Kind
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum KeyID {
     /// e.g. `0x0009,0x000`
@@ -75,21 +80,150 @@ pub enum KeyID {
     /// e.g. `0x0009+0x000`
     And(Vec<KeyID>),
     /// e.g. `0x0009`
-    One(String),
+    One(KeyCode),
     /// e.g. `!0,Right Attack/Block`
     Alias(String),
 }
 
+impl KeyID {
+    /// The same keys with every code written in its canonical form.
+    pub fn canonical(&self) -> Self {
+        match self {
+            KeyID::Or(keys) => KeyID::Or(keys.iter().map(KeyID::canonical).collect()),
+            KeyID::And(keys) => KeyID::And(keys.iter().map(KeyID::canonical).collect()),
+            KeyID::One(key) => KeyID::One(key.canonical()),
+            KeyID::Alias(alias) => KeyID::Alias(alias.clone()),
+        }
+    }
+}
+
+/// A numeric key code that remembers how it was written.
+///
+/// Codes compare and hash by value, so `0x000b` equals `0xb`, while `Display` reproduces the
+/// original spelling.
+///
+/// # Examples
+/// ```
+/// use controlmap_parser::parser::KeyCode;
+///
+/// let padded: KeyCode = "0x000b".parse()?;
+/// assert_eq!(padded, "0xb".parse()?);
+/// assert_eq!(padded.code(), 0xb);
+/// assert_eq!(padded.to_string(), "0x000b");
+/// assert_eq!(padded.canonical().to_string(), "0xb");
+/// # Ok::<(), controlmap_parser::scan_code::ScanCodeError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyCode {
+    code: u32,
+    raw: String,
+}
+
+impl KeyCode {
+    /// Create a code written in its canonical form, e.g. `0x1e`.
+    pub fn new(code: u32) -> Self {
+        Self {
+            code,
+            raw: format!("0x{:x}", code),
+        }
+    }
+
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
+    /// Original spelling, e.g. `0x000b`.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// The same code written as lowercase hex without leading zeros.
+    pub fn canonical(&self) -> Self {
+        Self::new(self.code)
+    }
+}
+
+impl PartialEq for KeyCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code
+    }
+}
+
+impl Eq for KeyCode {}
+
+impl core::hash::Hash for KeyCode {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.code.hash(state);
+    }
+}
+
+impl PartialOrd for KeyCode {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for KeyCode {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.code.cmp(&other.code)
+    }
+}
+
+impl From<u32> for KeyCode {
+    fn from(code: u32) -> Self {
+        Self::new(code)
+    }
+}
+
+impl FromStr for KeyCode {
+    type Err = ScanCodeError;
+
+    /// Parse the hex form used by `controlmap.txt`, e.g. `0x000b`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .ok_or_else(|| ScanCodeError::InvalidDigit(s.into()))?;
+        let code =
+            u32::from_str_radix(digits, 16).map_err(|_| ScanCodeError::InvalidDigit(s.into()))?;
+        Ok(Self {
+            code,
+            raw: s.into(),
+        })
+    }
+}
+
+impl fmt::Display for KeyCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for KeyCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KeyCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl fmt::Display for KeyID {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             KeyID::Or(keys) => {
                 let keys_str: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
-                write!(f, "{}", keys_str.join("+"))
+                write!(f, "{}", keys_str.join(","))
             }
             KeyID::And(keys) => {
                 let keys_str: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
-                write!(f, "{}", keys_str.join(","))
+                write!(f, "{}", keys_str.join("+"))
             }
             KeyID::One(key) => write!(f, "{}", key),
             KeyID::Alias(alias) => write!(f, "!0,{}", alias),
@@ -100,12 +234,24 @@ impl fmt::Display for KeyID {
 /// One line event
 ///
 /// e.g. `PickNext        0x09    0x1     0xff    0       0       0`
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventLine {
     pub event_name: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::names::lenient::keyboard")
+    )]
     pub keyboard_id: KeyID,
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::names::lenient::mouse")
+    )]
     pub mouse_id: KeyID,
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::names::lenient::gamepad")
+    )]
     pub gamepad_id: KeyID,
     pub remap_key: bool,
     pub remap_mouse: bool,
@@ -114,6 +260,51 @@ pub struct EventLine {
     pub event_binary_flag: Option<String>,
 }
 
+impl EventLine {
+    /// Key id column of `device`.
+    pub fn key_id(&self, device: Device) -> &KeyID {
+        match device {
+            Device::Keyboard => &self.keyboard_id,
+            Device::Mouse => &self.mouse_id,
+            Device::Gamepad => &self.gamepad_id,
+        }
+    }
+
+    /// Mutable key id column of `device`.
+    pub fn key_id_mut(&mut self, device: Device) -> &mut KeyID {
+        match device {
+            Device::Keyboard => &mut self.keyboard_id,
+            Device::Mouse => &mut self.mouse_id,
+            Device::Gamepad => &mut self.gamepad_id,
+        }
+    }
+
+    /// Whether the player may remap this event on `device`.
+    pub fn remappable(&self, device: Device) -> bool {
+        match device {
+            Device::Keyboard => self.remap_key,
+            Device::Mouse => self.remap_mouse,
+            Device::Gamepad => self.remap_gamepad,
+        }
+    }
+
+    /// Decode the user event flag column, `None` if the event has none.
+    ///
+    /// # Errors
+    /// The flag does not fit in 32 bits.
+    pub fn user_event_flag(&self) -> Result<Option<UserEventFlag>, ScanCodeError> {
+        self.event_binary_flag
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+    }
+
+    /// Replace the user event flag column with the hex form of `flag`.
+    pub fn set_user_event_flag(&mut self, flag: Option<UserEventFlag>) {
+        self.event_binary_flag = flag.map(|flag| flag.to_string());
+    }
+}
+
 impl fmt::Display for EventLine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -141,14 +332,21 @@ pub enum Line {
     EventLine(EventLine),
     /// new line `\n`
     BlankLine,
+    /// A line that could not be parsed, kept as is.
+    Invalid {
+        /// Original text without the line ending.
+        raw: String,
+        diagnostics: Vec<Diagnostic>,
+    },
 }
 
 impl fmt::Display for Line {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Line::Comment(comment) => writeln!(f, "// {}", comment.trim()),
+            Line::Comment(comment) => writeln!(f, "//{}", comment),
             Line::EventLine(event_line) => writeln!(f, "{}", event_line),
             Line::BlankLine => writeln!(f),
+            Line::Invalid { raw, .. } => writeln!(f, "{}", raw),
         }
     }
 }
@@ -198,7 +396,7 @@ fn parse_key_or(input: &str) -> IResult<&str, KeyID> {
 }
 
 fn parse_key_one(input: &str) -> IResult<&str, KeyID> {
-    map(parse_hex, |key| KeyID::One(key.into()))(input)
+    map(map_res(parse_hex, KeyCode::from_str), KeyID::One)(input)
 }
 
 fn parse_key_alias(input: &str) -> IResult<&str, KeyID> {
@@ -214,7 +412,43 @@ fn parse_key_id(input: &str) -> IResult<&str, KeyID> {
 fn parse_event_name(input: &str) -> IResult<&str, &str> {
     context(
         "Expected ident. non tab any string",
-        take_while1(|c: char| c != '\t'),
+        take_while1(|c: char| !matches!(c, '\t' | '\r' | '\n')),
+    )(input)
+}
+
+/// Peek that the field ends here: separator, line ending or end of input.
+fn field_end(input: &str) -> IResult<&str, &str> {
+    peek(alt((recognize(one_of(" \t")), line_ending, eof)))(input)
+}
+
+/// Tab separated field labeled with the diagnostic it produces on failure, together with its
+/// text without the spaces in front of it.
+fn field<'a, O, F>(
+    code: DiagnosticCode,
+    parser: F,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (&'a str, O)>
+where
+    F: FnMut(&'a str) -> IResult<&'a str, O>,
+{
+    context(
+        code.label(),
+        preceded(
+            tab_space1,
+            terminated(
+                map(consumed(parser), |(text, out)| {
+                    (text.trim_start_matches(' '), out)
+                }),
+                field_end,
+            ),
+        ),
+    )
+}
+
+/// Trailing spaces and the line ending (or end of input).
+fn parse_line_end(input: &str) -> IResult<&str, &str> {
+    context(
+        DiagnosticCode::UnexpectedToken.label(),
+        preceded(space0, alt((line_ending, eof))),
     )(input)
 }
 
@@ -224,41 +458,60 @@ fn parse_comment_line(input: &str) -> IResult<&str, Line> {
     Ok((input, Line::Comment(comment.into())))
 }
 
-fn parse_event_line(input: &str) -> IResult<&str, Line> {
-    let (input, event_name) = parse_event_name(input)?;
-    let (input, _) = tab_space1(input)?;
-    let (input, keyboard_id) = parse_key_id(input)?;
-    let (input, _) = tab_space1(input)?;
-    let (input, mouse_id) = parse_key_id(input)?;
-    let (input, _) = tab_space1(input)?;
-    let (input, gamepad_id) = parse_key_id(input)?;
-    let (input, _) = tab_space1(input)?;
-    let (input, remap_key) = parse_flag(input)?;
-    let (input, _) = tab_space1(input)?;
-    let (input, remap_mouse) = parse_flag(input)?;
-    let (input, _) = tab_space1(input)?;
-    let (input, remap_gamepad) = parse_flag(input)?;
-    let (input, event_binary_flag) = opt(preceded(tab_space1, parse_hex))(input)?;
-    let (input, _) = space0(input)?; // ' ' or \t
-    let (input, _) = preceded(opt(tag("\r")), opt(tag("\n")))(input)?;
-
-    Ok((
-        input,
-        Line::EventLine(EventLine {
-            event_name: event_name.into(),
-            keyboard_id,
-            mouse_id,
-            gamepad_id,
-            remap_key,
-            remap_mouse,
-            remap_gamepad,
-            event_binary_flag: event_binary_flag.map(|event| event.into()),
-        }),
-    ))
+/// Event line with the text of each of its fields, which are slices of `input`.
+fn parse_event_line(input: &str) -> IResult<&str, (Line, Vec<&str>)> {
+    let (input, event_name) = context(
+        DiagnosticCode::InvalidEventName.label(),
+        terminated(parse_event_name, peek(tab_space1)),
+    )(input)?;
+    let (input, (keyboard_text, keyboard_id)) =
+        field(DiagnosticCode::InvalidKeyboardId, parse_key_id)(input)?;
+    let (input, (mouse_text, mouse_id)) =
+        field(DiagnosticCode::InvalidMouseId, parse_key_id)(input)?;
+    let (input, (gamepad_text, gamepad_id)) =
+        field(DiagnosticCode::InvalidGamepadId, parse_key_id)(input)?;
+    let (input, (remap_key_text, remap_key)) =
+        field(DiagnosticCode::InvalidRemapKey, parse_flag)(input)?;
+    let (input, (remap_mouse_text, remap_mouse)) =
+        field(DiagnosticCode::InvalidRemapMouse, parse_flag)(input)?;
+    let (input, (remap_gamepad_text, remap_gamepad)) =
+        field(DiagnosticCode::InvalidRemapGamepad, parse_flag)(input)?;
+    let mut fields = vec![
+        event_name,
+        keyboard_text,
+        mouse_text,
+        gamepad_text,
+        remap_key_text,
+        remap_mouse_text,
+        remap_gamepad_text,
+    ];
+    // The flag is optional, but anything other than the line end must be a valid flag.
+    let (input, event_binary_flag) = match parse_line_end(input) {
+        Ok(_) => (input, None),
+        Err(_) => {
+            let (input, (_, flag)) =
+                field(DiagnosticCode::InvalidEventBinaryFlag, parse_hex)(input)?;
+            fields.push(flag);
+            (input, Some(flag))
+        }
+    };
+    let (input, _) = parse_line_end(input)?;
+
+    let event_line = EventLine {
+        event_name: event_name.into(),
+        keyboard_id,
+        mouse_id,
+        gamepad_id,
+        remap_key,
+        remap_mouse,
+        remap_gamepad,
+        event_binary_flag: event_binary_flag.map(|event| event.into()),
+    };
+    Ok((input, (Line::EventLine(event_line), fields)))
 }
 
 fn parse_blank_line(input: &str) -> IResult<&str, Line> {
-    let (input, _) = multispace1(input)?;
+    let (input, _) = alt((preceded(space0, line_ending), preceded(space1, eof)))(input)?;
     Ok((input, Line::BlankLine))
 }
 
@@ -285,9 +538,9 @@ fn parse_blank_line(input: &str) -> IResult<&str, Line> {
 ///         Line::Comment(" Main Gameplay".into()),
 ///         Line::EventLine(EventLine {
 ///             event_name: "Forward".into(),
-///             keyboard_id: KeyID::One("0x11".into()),
-///             mouse_id: KeyID::One("0xff".into()),
-///             gamepad_id: KeyID::One("0xff".into()),
+///             keyboard_id: KeyID::One("0x11".parse().unwrap()),
+///             mouse_id: KeyID::One("0xff".parse().unwrap()),
+///             gamepad_id: KeyID::One("0xff".parse().unwrap()),
 ///             remap_key: true,
 ///             remap_mouse: true,
 ///             remap_gamepad: false,
@@ -295,9 +548,9 @@ fn parse_blank_line(input: &str) -> IResult<&str, Line> {
 ///         }),
 ///         Line::EventLine(EventLine {
 ///             event_name: "Back".into(),
-///             keyboard_id: KeyID::One("0x1f".into()),
-///             mouse_id: KeyID::One("0xff".into()),
-///             gamepad_id: KeyID::One("0xff".into()),
+///             keyboard_id: KeyID::One("0x1f".parse().unwrap()),
+///             mouse_id: KeyID::One("0xff".parse().unwrap()),
+///             gamepad_id: KeyID::One("0xff".parse().unwrap()),
 ///             remap_key: true,
 ///             remap_mouse: true,
 ///             remap_gamepad: false,
@@ -307,8 +560,8 @@ fn parse_blank_line(input: &str) -> IResult<&str, Line> {
 ///         Line::EventLine(EventLine {
 ///             event_name: "Accept".into(),
 ///             keyboard_id: KeyID::Alias("Activate".into()),
-///             mouse_id: KeyID::One("0xff".into()),
-///             gamepad_id: KeyID::One("0x2000".into()),
+///             mouse_id: KeyID::One("0xff".parse().unwrap()),
+///             gamepad_id: KeyID::One("0x2000".parse().unwrap()),
 ///             remap_key: false,
 ///             remap_mouse: false,
 ///             remap_gamepad: false,
@@ -319,10 +572,59 @@ fn parse_blank_line(input: &str) -> IResult<&str, Line> {
 /// assert_eq!(actual, expected);
 /// ```
 pub fn control_map_parser(input: &str) -> IResult<&str, Vec<Line>> {
-    let parse_line = alt((parse_blank_line, parse_comment_line, parse_event_line));
     many0(parse_line)(input)
 }
 
+/// Parse controlmap.txt without stopping at broken lines.
+///
+/// Each line that fails to parse is kept as [`Line::Invalid`] and parsing resumes on the next
+/// line, so every problem in the file is collected in one pass.
+///
+/// # Examples
+/// ```
+/// use controlmap_parser::diagnostic::DiagnosticCode;
+/// use controlmap_parser::parser::{control_map_parser_tolerant, Line};
+///
+/// let input = "Forward\t0x11\t0xff\t0xff\t1\t1\t0\t0x801
+/// Back\t0x1f\t0xff\t0xff\t1\t1
+/// Jump\t0x39\t0xff\t0x1000\t1\t1\t1\t0xzz
+/// ";
+/// let (lines, diagnostics) = control_map_parser_tolerant(input);
+///
+/// assert_eq!(lines.len(), 3);
+/// assert!(matches!(lines[0], Line::EventLine(_)));
+/// assert!(matches!(&lines[1], Line::Invalid { raw, .. } if raw == "Back\t0x1f\t0xff\t0xff\t1\t1"));
+///
+/// let codes: Vec<_> = diagnostics.iter().map(|d| (d.line, d.code)).collect();
+/// assert_eq!(
+///     codes,
+///     [
+///         (2, DiagnosticCode::InvalidRemapGamepad),
+///         (3, DiagnosticCode::InvalidEventBinaryFlag)
+///     ]
+/// );
+/// ```
+pub fn control_map_parser_tolerant(input: &str) -> (Vec<Line>, Vec<Diagnostic>) {
+    let tree = cst::SyntaxTree::parse(input);
+    let diagnostics = tree.diagnostics().cloned().collect();
+    (tree.to_lines(), diagnostics)
+}
+
+/// Parse one line of any kind.
+pub(crate) fn parse_line(input: &str) -> IResult<&str, Line> {
+    map(parse_line_fields, |(line, _)| line)(input)
+}
+
+/// Parse one line of any kind, with the fields of an event line as slices of `input`.
+pub(crate) fn parse_line_fields(input: &str) -> IResult<&str, (Line, Vec<&str>)> {
+    let no_fields = |line| (line, Vec::new());
+    alt((
+        map(parse_blank_line, no_fields),
+        map(parse_comment_line, no_fields),
+        parse_event_line,
+    ))(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -347,7 +649,7 @@ mod tests {
     #[test]
     fn test_parse_key_id_one() {
         let input = "0x1234";
-        let expected_output = Ok(("", KeyID::One("0x1234".into())));
+        let expected_output = Ok(("", KeyID::One("0x1234".parse().unwrap())));
         let result = parse_key_id(input);
         assert_eq!(result, expected_output);
     }
@@ -358,9 +660,9 @@ mod tests {
         let expected_output = Ok((
             "",
             KeyID::And(vec![
-                KeyID::One("0x1234".into()),
-                KeyID::One("0x5678".into()),
-                KeyID::One("0x9abc".into()),
+                KeyID::One("0x1234".parse().unwrap()),
+                KeyID::One("0x5678".parse().unwrap()),
+                KeyID::One("0x9abc".parse().unwrap()),
             ]),
         ));
         let result = parse_key_id(input);
@@ -373,11 +675,17 @@ mod tests {
         let expected_output = Ok((
             "",
             KeyID::Or(vec![
-                KeyID::And(vec![KeyID::One("0x2a".into()), KeyID::One("0x0f".into())]),
-                KeyID::And(vec![KeyID::One("0x36".into()), KeyID::One("0x0".into())]),
-                KeyID::One("0x1234".into()),
-                KeyID::One("0x5678".into()),
-                KeyID::One("0x9abc".into()),
+                KeyID::And(vec![
+                    KeyID::One("0x2a".parse().unwrap()),
+                    KeyID::One("0x0f".parse().unwrap()),
+                ]),
+                KeyID::And(vec![
+                    KeyID::One("0x36".parse().unwrap()),
+                    KeyID::One("0x0".parse().unwrap()),
+                ]),
+                KeyID::One("0x1234".parse().unwrap()),
+                KeyID::One("0x5678".parse().unwrap()),
+                KeyID::One("0x9abc".parse().unwrap()),
             ]),
         ));
         let result = parse_key_id(input);
@@ -391,4 +699,60 @@ mod tests {
         let result = parse_comment_line(input);
         assert_eq!(result, expected_output);
     }
+
+    #[test]
+    fn test_blank_lines_are_not_merged() {
+        let (rest, lines) = control_map_parser("\n \t\r\n\n// x\n").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            lines,
+            [
+                Line::BlankLine,
+                Line::BlankLine,
+                Line::BlankLine,
+                Line::Comment(" x".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comment_prints_as_read() {
+        let (_, lines) = control_map_parser("//Main Gameplay\n//  Menu Mode  \n").unwrap();
+        let printed: String = lines.iter().map(ToString::to_string).collect();
+        assert_eq!(printed, "//Main Gameplay\n//  Menu Mode  \n");
+    }
+
+    #[test]
+    fn test_parse_event_line_error_points_at_field() {
+        let input = "Forward\t0x11\t0xff\t0xff\t1\t2\t0\t0x801\n";
+        let err = parse_event_line(input).unwrap_err();
+        let diagnostic = Diagnostic::from_nom(input, err);
+        assert_eq!(diagnostic.code, DiagnosticCode::InvalidRemapMouse);
+        assert_eq!(diagnostic.span, 25..26);
+    }
+
+    #[test]
+    fn test_parse_event_line_rejects_trailing_garbage() {
+        let input = "Forward\t0x11\t0xff\t0xff\t1\t1\t0\t0x801 junk\n";
+        let err = parse_event_line(input).unwrap_err();
+        let diagnostic = Diagnostic::from_nom(input, err);
+        assert_eq!(diagnostic.code, DiagnosticCode::UnexpectedToken);
+        assert_eq!(&input[diagnostic.span], "junk");
+    }
+
+    #[test]
+    fn test_key_code_compares_by_value() {
+        let (_, (padded, _)) = parse_event_line("Rotate\t0xff\t0xff\t0x000b\t0\t0\t0\n").unwrap();
+        let (_, (short, _)) = parse_event_line("Rotate\t0xff\t0xff\t0xb\t0\t0\t0\n").unwrap();
+        assert_eq!(padded, short);
+        assert_eq!(
+            padded.to_string(),
+            "Rotate\t0xff\t0xff\t0x000b\t0\t0\t0\t\n"
+        );
+
+        let input = "Rotate\t0x1ffffffff\t0xff\t0xb\t0\t0\t0\n";
+        let err = parse_event_line(input).unwrap_err();
+        let diagnostic = Diagnostic::from_nom(input, err);
+        assert_eq!(diagnostic.code, DiagnosticCode::InvalidKeyboardId);
+    }
 }
@@ -0,0 +1,314 @@
+//! Lossless concrete syntax tree.
+//!
+//! Every byte of the input belongs to a token, its trivia or a line ending, so printing a
+//! [`SyntaxTree`] reproduces the input byte-for-byte, including tab runs, trailing spaces and
+//! `\r\n` endings. The [`Line`] AST of each line and the spans of its tokens come from the
+//! same parse.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::parser::{cst::SyntaxTree, Line};
+//!
+//! let input = "//  Main Gameplay\r\nForward\t\t\t\t0x11\t\t0xff\t0xff\t\t\t1\t1\t0\t0x801  \r\n\t\r\n";
+//! let tree = SyntaxTree::parse(input);
+//!
+//! assert_eq!(tree.to_string(), input);
+//! assert_eq!(tree.lines()[1].tokens()[0].text(), "Forward");
+//! assert_eq!(tree.lines()[1].tokens()[0].trailing(), "\t\t\t\t");
+//! assert_eq!(tree.lines()[1].tokens()[7].trailing(), "  ");
+//! assert_eq!(tree.lines()[2].line(), &Line::BlankLine);
+//! ```
+use super::{parse_line_fields, Line};
+use crate::diagnostic::Diagnostic;
+use core::fmt;
+use core::ops::Range;
+use nom::Offset;
+
+/// A whole `controlmap.txt` with its formatting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTree {
+    lines: Vec<SyntaxLine>,
+}
+
+/// One physical line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxLine {
+    node: LineNode,
+    line_ending: String,
+    span: Range<usize>,
+    line: Line,
+}
+
+/// The syntax of a line body, i.e. without its line ending.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineNode {
+    /// Nothing but spaces and tabs.
+    Blank { whitespace: String },
+    /// `//` followed by `text`.
+    Comment { text: String },
+    /// Event name followed by its tab separated fields.
+    Event { tokens: Vec<Token> },
+    /// A line that could not be parsed.
+    Invalid { raw: String },
+}
+
+/// A field of an event line and the whitespace that follows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    text: String,
+    trailing: String,
+    span: Range<usize>,
+}
+
+impl SyntaxTree {
+    /// Parse without ever failing. Broken lines become [`LineNode::Invalid`].
+    pub fn parse(input: &str) -> Self {
+        let mut lines = Vec::new();
+
+        let mut offset = 0;
+        while offset < input.len() {
+            let rest = &input[offset..];
+            let raw_len = rest.find('\n').map_or(rest.len(), |pos| pos + 1);
+            let raw = &rest[..raw_len];
+            let ending_len = match raw {
+                _ if raw.ends_with("\r\n") => 2,
+                _ if raw.ends_with('\n') => 1,
+                _ => 0,
+            };
+            let text = &raw[..raw_len - ending_len];
+
+            let (line, fields) = match parse_line_fields(rest) {
+                Ok((_, parsed)) => parsed,
+                Err(err) => {
+                    let line = Line::Invalid {
+                        raw: text.into(),
+                        diagnostics: vec![Diagnostic::from_nom(input, err)],
+                    };
+                    (line, Vec::new())
+                }
+            };
+            let node = match &line {
+                Line::BlankLine => LineNode::Blank {
+                    whitespace: text.into(),
+                },
+                Line::Comment(comment) => LineNode::Comment {
+                    text: comment.clone(),
+                },
+                Line::EventLine(_) => {
+                    let fields: Vec<_> = fields
+                        .iter()
+                        .map(|field| rest.offset(field)..rest.offset(field) + field.len())
+                        .collect();
+                    LineNode::Event {
+                        tokens: tokens(text, offset, &fields),
+                    }
+                }
+                Line::Invalid { raw, .. } => LineNode::Invalid { raw: raw.clone() },
+            };
+
+            lines.push(SyntaxLine {
+                node,
+                line_ending: raw[text.len()..].into(),
+                span: offset..offset + raw_len,
+                line,
+            });
+            offset += raw_len;
+        }
+
+        Self { lines }
+    }
+
+    /// Lines in source order.
+    pub fn lines(&self) -> &[SyntaxLine] {
+        &self.lines
+    }
+
+    /// Derive the AST.
+    pub fn to_lines(&self) -> Vec<Line> {
+        self.lines.iter().map(|line| line.line.clone()).collect()
+    }
+
+    /// Diagnostics of every line that could not be parsed.
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.lines.iter().flat_map(|line| match &line.line {
+            Line::Invalid { diagnostics, .. } => diagnostics.as_slice(),
+            _ => &[],
+        })
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            write!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl SyntaxLine {
+    pub fn node(&self) -> &LineNode {
+        &self.node
+    }
+
+    /// `"\r\n"`, `"\n"` or `""` for a last line without one.
+    pub fn line_ending(&self) -> &str {
+        &self.line_ending
+    }
+
+    /// Byte range in the source, including the line ending.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// AST of this line.
+    pub fn line(&self) -> &Line {
+        &self.line
+    }
+
+    /// Fields of an event line. Empty for other lines.
+    pub fn tokens(&self) -> &[Token] {
+        match &self.node {
+            LineNode::Event { tokens } => tokens,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for SyntaxLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.node, self.line_ending)
+    }
+}
+
+impl fmt::Display for LineNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineNode::Blank { whitespace } => write!(f, "{}", whitespace),
+            LineNode::Comment { text } => write!(f, "//{}", text),
+            LineNode::Event { tokens } => {
+                for token in tokens {
+                    write!(f, "{}", token)?;
+                }
+                Ok(())
+            }
+            LineNode::Invalid { raw } => write!(f, "{}", raw),
+        }
+    }
+}
+
+impl Token {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Tabs and spaces between the text and the next field or the line ending.
+    pub fn trailing(&self) -> &str {
+        &self.trailing
+    }
+
+    /// Byte range of the text in the source.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.text, self.trailing)
+    }
+}
+
+/// Tokens of an event line body with `fields` at the given byte ranges of `text`, each
+/// followed by the whitespace up to the next field or the end of the line. `offset` is the
+/// position of `text` in the source.
+fn tokens(text: &str, offset: usize, fields: &[Range<usize>]) -> Vec<Token> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let next = fields.get(idx + 1).map_or(text.len(), |next| next.start);
+            Token {
+                text: text[field.clone()].into(),
+                trailing: text[field.end..next].into(),
+                span: offset + field.start..offset + field.end,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_round_trip_vanilla() {
+        let input = include_str!("../../examples/controlmap.txt");
+        let tree = SyntaxTree::parse(input);
+        assert_eq!(tree.to_string(), input);
+        assert_eq!(tree.diagnostics().count(), 0);
+    }
+
+    #[test]
+    fn test_round_trip_invalid_line() {
+        let input = "Forward 0x11\r\n  \n// Menu Mode";
+        let tree = SyntaxTree::parse(input);
+        assert_eq!(tree.to_string(), input);
+        assert_eq!(tree.diagnostics().count(), 1);
+        assert_eq!(
+            tree.to_lines(),
+            [
+                Line::Invalid {
+                    raw: "Forward 0x11".into(),
+                    diagnostics: tree.diagnostics().cloned().collect(),
+                },
+                Line::BlankLine,
+                Line::Comment(" Menu Mode".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokens_follow_the_parse() {
+        let input = "// x\nJump\t\t  0x39\t0xff\t0x1000\t1\t1\t1\t0xc01 \t \r\nAccept\t!0,Ready Weapon\t0xff\t0xff\t0\t0\t0";
+        let tree = SyntaxTree::parse(input);
+        assert_eq!(tree.to_string(), input);
+
+        let jump = &tree.lines()[1];
+        let actual: Vec<_> = jump
+            .tokens()
+            .iter()
+            .map(|token| (token.text(), token.trailing(), &input[token.span()]))
+            .collect();
+        assert_eq!(
+            actual,
+            [
+                ("Jump", "\t\t  ", "Jump"),
+                ("0x39", "\t", "0x39"),
+                ("0xff", "\t", "0xff"),
+                ("0x1000", "\t", "0x1000"),
+                ("1", "\t", "1"),
+                ("1", "\t", "1"),
+                ("1", "\t", "1"),
+                ("0xc01", " \t ", "0xc01"),
+            ]
+        );
+        // Tokens cover the line body, and each field of the AST is the text of its token.
+        let body: String = jump.tokens().iter().map(ToString::to_string).collect();
+        assert_eq!(body + jump.line_ending(), &input[jump.span()]);
+        let Line::EventLine(event) = jump.line() else {
+            panic!("expected an event line");
+        };
+        assert_eq!(event.keyboard_id.to_string(), jump.tokens()[1].text());
+        assert_eq!(
+            event.event_binary_flag.as_deref(),
+            Some(jump.tokens()[7].text())
+        );
+
+        let accept = &tree.lines()[2];
+        assert_eq!(accept.tokens()[1].text(), "!0,Ready Weapon");
+        assert_eq!(accept.tokens()[6].trailing(), "");
+    }
+}
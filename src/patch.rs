@@ -0,0 +1,560 @@
+//! Declarative patches.
+//!
+//! A patch lists only the changes a mod makes, so patches from several mods can be applied
+//! one after the other instead of each shipping a whole `controlmap.txt`. One statement per
+//! line; `#` and `//` start a comment.
+//!
+//! ```text
+//! [Main Gameplay]                      # context of the following statements
+//! Sprint.keyboard = LAlt               # key names of `scan_code`, or hex codes
+//! Shout.gamepad = _360_LB+_360_A, 0x0009
+//! Activate.mouse = !0,Ready Weapon     # alias
+//! Sprint.mouse.remappable = false
+//! flag Jump += Jumping | Sneaking      # also `-=`, and `=` with names, hex or `none`
+//! [Menu Mode] remove Cancel            # a context may prefix a statement
+//! add Dodge	0x38	0xff	0xff	1	1	1	0x801
+//! ```
+//!
+//! `add` takes an event line as written in `controlmap.txt`, tabs included. Statements
+//! before any context apply to Main Gameplay; contexts past the known ones are written `[#17]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{context::ContextKind, patch::Patch, ControlMap};
+//!
+//! let mut control_map = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+//! let patch: Patch = "\
+//! [Main Gameplay]
+//! Sprint.keyboard = LAlt
+//! flag Jump -= Vats
+//! [Menu Mode] remove Cancel
+//! remove Dodge
+//! "
+//! .parse()?;
+//!
+//! let failures = control_map.apply_patch(&patch);
+//! assert_eq!(failures.len(), 1);
+//! assert_eq!(failures[0].error.to_string(), "Event `Dodge` not found in context `Menu Mode`");
+//!
+//! let main = control_map.context(ContextKind::MainGameplay).unwrap();
+//! assert_eq!(main.event("Sprint").unwrap().keyboard_id.to_string(), "0x38");
+//! assert_eq!(main.event("Jump").unwrap().event_binary_flag.as_deref(), Some("0x401"));
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::{ControlMap, ControlMapError, Result};
+use crate::diff::Change;
+use crate::names::{device_from_name, flag_text, parse_flag, parse_key_id, NamedKeys};
+use crate::parser::{parse_line, EventLine, KeyID, Line};
+use crate::scan_code::Device;
+use crate::user_event::UserEventFlag;
+use core::{fmt, str::FromStr};
+use nom::{
+    bytes::complete::{tag, take_till1},
+    character::complete::{char, space0, space1},
+    sequence::{delimited, terminated},
+};
+
+type IResult<'a, O> = nom::IResult<&'a str, O>;
+
+/// Statements in the order they are applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Patch {
+    pub statements: Vec<Statement>,
+}
+
+/// One change to an event of a context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Statement {
+    pub context: ContextKind,
+    pub op: PatchOp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PatchOp {
+    /// `Event.device = keys`
+    SetBinding {
+        event: String,
+        device: Device,
+        key_id: KeyID,
+    },
+    /// `Event.device.remappable = true`
+    SetRemappable {
+        event: String,
+        device: Device,
+        remappable: bool,
+    },
+    /// `flag Event = Movement | Jumping`, `None` for `none`.
+    SetFlag {
+        event: String,
+        flag: Option<UserEventFlag>,
+    },
+    /// `flag Event += Jumping`
+    AddFlag { event: String, flag: UserEventFlag },
+    /// `flag Event -= Jumping`
+    RemoveFlag { event: String, flag: UserEventFlag },
+    /// `add` followed by an event line.
+    AddEvent(EventLine),
+    /// `remove Event`
+    RemoveEvent(String),
+}
+
+/// A statement [`ControlMap::apply_patch`] could not apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PatchFailure {
+    /// Index in [`Patch::statements`].
+    pub index: usize,
+    pub statement: Statement,
+    pub error: ControlMapError,
+}
+
+impl Patch {
+    /// Statements that turn `old` into `new`.
+    ///
+    /// Added events go to the end of their context; other reordering cannot be expressed and
+    /// is left out.
+    ///
+    /// # Errors
+    /// A user event flag of `new` does not fit in 32 bits.
+    ///
+    /// # Examples
+    /// ```
+    /// use controlmap_parser::{patch::Patch, ControlMap};
+    ///
+    /// let old = ControlMap::from_txt("Sprint\t0x2a\t0xff\t0x0040\t1\t1\t1\t0x801\n")?;
+    /// let new = ControlMap::from_txt("Sprint\t0x38\t0xff\t0x0040\t1\t0\t1\t0xc01\n")?;
+    /// assert_eq!(
+    ///     Patch::between(&old, &new)?.to_string(),
+    ///     "\
+    /// [Main Gameplay]
+    /// Sprint.keyboard = LAlt
+    /// Sprint.mouse.remappable = false
+    /// flag Sprint = Movement | Jumping | Vats
+    /// "
+    /// );
+    /// # Ok::<(), controlmap_parser::ControlMapError>(())
+    /// ```
+    pub fn between(old: &ControlMap, new: &ControlMap) -> Result<Self> {
+        let mut statements = Vec::new();
+        for change in old.diff(new).changes {
+            let (context, op) = match change {
+                Change::EventAdded { context, event } => {
+                    let Some(event) = new.context(context).and_then(|c| c.event(&event)) else {
+                        continue;
+                    };
+                    (context, PatchOp::AddEvent(event.clone()))
+                }
+                Change::EventRemoved { context, event } => (context, PatchOp::RemoveEvent(event)),
+                Change::BindingChanged {
+                    context,
+                    event,
+                    device,
+                    new,
+                    ..
+                } => (
+                    context,
+                    PatchOp::SetBinding {
+                        event,
+                        device,
+                        key_id: new,
+                    },
+                ),
+                Change::RemapChanged {
+                    context,
+                    event,
+                    device,
+                    new,
+                    ..
+                } => (
+                    context,
+                    PatchOp::SetRemappable {
+                        event,
+                        device,
+                        remappable: new,
+                    },
+                ),
+                Change::FlagChanged { context, event, .. }
+                | Change::RawFlagChanged { context, event, .. } => {
+                    let Some(new_event) = new.context(context).and_then(|c| c.event(&event)) else {
+                        continue;
+                    };
+                    let flag = new_event.user_event_flag()?;
+                    (context, PatchOp::SetFlag { event, flag })
+                }
+                Change::EventMoved { .. } => continue,
+            };
+            statements.push(Statement { context, op });
+        }
+        Ok(Self { statements })
+    }
+}
+
+impl ControlMap {
+    /// Apply every statement of `patch` that can be, returning the others with the reason.
+    pub fn apply_patch(&mut self, patch: &Patch) -> Vec<PatchFailure> {
+        let mut failures = Vec::new();
+        for (index, statement) in patch.statements.iter().enumerate() {
+            if let Err(error) = self.apply_statement(statement) {
+                failures.push(PatchFailure {
+                    index,
+                    statement: statement.clone(),
+                    error,
+                });
+            }
+        }
+        failures
+    }
+
+    fn apply_statement(&mut self, statement: &Statement) -> Result<()> {
+        let context = statement.context;
+        match &statement.op {
+            PatchOp::SetBinding {
+                event,
+                device,
+                key_id,
+            } => self
+                .set_binding(context, event, *device, key_id.clone())
+                .map(drop),
+            PatchOp::SetRemappable {
+                event,
+                device,
+                remappable,
+            } => {
+                let event = self.event_mut(context, event)?;
+                match device {
+                    Device::Keyboard => event.remap_key = *remappable,
+                    Device::Mouse => event.remap_mouse = *remappable,
+                    Device::Gamepad => event.remap_gamepad = *remappable,
+                }
+                Ok(())
+            }
+            PatchOp::SetFlag { event, flag } => {
+                self.event_mut(context, event)?.set_user_event_flag(*flag);
+                Ok(())
+            }
+            PatchOp::AddFlag { event, flag } => {
+                let event = self.event_mut(context, event)?;
+                let current = event.user_event_flag()?.unwrap_or(UserEventFlag::empty());
+                event.set_user_event_flag(Some(current | *flag));
+                Ok(())
+            }
+            PatchOp::RemoveFlag { event, flag } => {
+                let event = self.event_mut(context, event)?;
+                let current = event.user_event_flag()?.unwrap_or(UserEventFlag::empty());
+                event.set_user_event_flag(Some(current - *flag));
+                Ok(())
+            }
+            PatchOp::AddEvent(event) => self.insert_event(context, event.clone()),
+            PatchOp::RemoveEvent(event) => self.remove_event(context, event).map(drop),
+        }
+    }
+
+    fn event_mut(&mut self, context: ContextKind, name: &str) -> Result<&mut EventLine> {
+        let position = self.find_event(context, name)?;
+        match &mut self.lines[position] {
+            Line::EventLine(event) => Ok(event),
+            _ => unreachable!("find_event only returns event lines"),
+        }
+    }
+}
+
+impl FromStr for Patch {
+    type Err = ControlMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut context = ContextKind::MainGameplay;
+        let mut statements = Vec::new();
+
+        for (idx, line) in s.lines().enumerate() {
+            let invalid = |message: &str| ControlMapError::InvalidPatch {
+                line: idx + 1,
+                message: message.into(),
+            };
+            let mut line = strip_comment(line).trim();
+
+            if let Ok((rest, section)) = parse_section(line) {
+                context = context_from_name(section).ok_or_else(|| invalid("unknown context"))?;
+                line = rest;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let op = parse_op(line).map_err(invalid)?;
+            statements.push(Statement { context, op });
+        }
+        Ok(Self { statements })
+    }
+}
+
+/// Cut a `#` or `//` comment starting a line or a word, except in the event line of `add`.
+fn strip_comment(line: &str) -> &str {
+    if line.trim_start().starts_with("add") {
+        return line;
+    }
+    let end = line
+        .char_indices()
+        .find(|&(idx, _)| {
+            let rest = &line[idx..];
+            let after_space = !line[..idx].ends_with(|c: char| !c.is_whitespace());
+            after_space && (rest.starts_with('#') || rest.starts_with("//"))
+        })
+        .map_or(line.len(), |(idx, _)| idx);
+    &line[..end]
+}
+
+fn parse_section(input: &str) -> IResult<'_, &str> {
+    terminated(
+        delimited(char('['), take_till1(|c| c == ']'), char(']')),
+        space0,
+    )(input)
+}
+
+fn context_from_name(name: &str) -> Option<ContextKind> {
+    match name.trim().strip_prefix('#') {
+        Some(index) => index.parse().ok().map(ContextKind::from_index),
+        None => ContextKind::from_header(name),
+    }
+}
+
+fn context_name(context: ContextKind) -> String {
+    match context {
+        ContextKind::Other(index) => format!("#{}", index),
+        known => known.name().into(),
+    }
+}
+
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<'a, &'a str> {
+    terminated(tag(word), space1)
+}
+
+fn parse_op(line: &str) -> Result<PatchOp, &'static str> {
+    if let Ok((rest, _)) = keyword("remove")(line) {
+        return Ok(PatchOp::RemoveEvent(rest.trim().into()));
+    }
+    if let Ok((rest, _)) = keyword("add")(line) {
+        return match parse_line(rest) {
+            Ok(("", Line::EventLine(event))) => Ok(PatchOp::AddEvent(event)),
+            _ => Err("expected an event line of controlmap.txt after `add`"),
+        };
+    }
+    if let Ok((rest, _)) = keyword("flag")(line) {
+        let (target, value) = rest.split_once('=').ok_or("expected `=`, `+=` or `-=`")?;
+        let target = target.trim_end();
+        let value = value.trim();
+        let flag = || parse_flag(value).ok_or("expected flag names or a hex value");
+
+        return Ok(if let Some(event) = target.strip_suffix('+') {
+            PatchOp::AddFlag {
+                event: event.trim_end().into(),
+                flag: flag()?,
+            }
+        } else if let Some(event) = target.strip_suffix('-') {
+            PatchOp::RemoveFlag {
+                event: event.trim_end().into(),
+                flag: flag()?,
+            }
+        } else {
+            let flag = match value.eq_ignore_ascii_case("none") {
+                true => None,
+                false => Some(flag()?),
+            };
+            PatchOp::SetFlag {
+                event: target.into(),
+                flag,
+            }
+        });
+    }
+
+    let (target, value) = line
+        .split_once('=')
+        .ok_or("expected `Event.device = keys`, `flag`, `add` or `remove`")?;
+    let (target, value) = (target.trim_end(), value.trim());
+    let (left, last) = target
+        .rsplit_once('.')
+        .ok_or("expected `Event.device` before `=`")?;
+
+    if last == "remappable" {
+        let (event, device) = left
+            .rsplit_once('.')
+            .ok_or("expected `Event.device.remappable`")?;
+        let remappable = match value {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => return Err("expected `true` or `false`"),
+        };
+        return Ok(PatchOp::SetRemappable {
+            event: event.into(),
+            device: device_from_name(device).ok_or("unknown device")?,
+            remappable,
+        });
+    }
+
+    let device = device_from_name(last).ok_or("unknown device")?;
+    let key_id = parse_key_id(device, value).ok_or("expected key names or hex codes")?;
+    Ok(PatchOp::SetBinding {
+        event: left.into(),
+        device,
+        key_id,
+    })
+}
+
+impl fmt::Display for PatchOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SetBinding {
+                event,
+                device,
+                key_id,
+            } => write!(f, "{event}.{device} = {}", NamedKeys(*device, key_id)),
+            Self::SetRemappable {
+                event,
+                device,
+                remappable,
+            } => write!(f, "{event}.{device}.remappable = {remappable}"),
+            Self::SetFlag { event, flag: None } => write!(f, "flag {event} = none"),
+            Self::SetFlag {
+                event,
+                flag: Some(flag),
+            } => write!(f, "flag {event} = {}", flag_text(*flag)),
+            Self::AddFlag { event, flag } => write!(f, "flag {event} += {}", flag_text(*flag)),
+            Self::RemoveFlag { event, flag } => {
+                write!(f, "flag {event} -= {}", flag_text(*flag))
+            }
+            Self::AddEvent(event) => write!(f, "add {}", event),
+            Self::RemoveEvent(event) => write!(f, "remove {event}"),
+        }
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut context = None;
+        for statement in &self.statements {
+            if context != Some(statement.context) {
+                writeln!(f, "[{}]", context_name(statement.context))?;
+                context = Some(statement.context);
+            }
+            writeln!(f, "{}", statement.op)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_statements() {
+        let patch: Patch = "\
+# Dodge mod
+Shout.gamepad = _360_LB+_360_A, 0x0009   // chord or trigger
+[Console] Auto-Move.mouse.remappable = 0
+[#20]
+flag Auto-Move -= main_four | Movement
+flag Auto-Move = none
+add Dodge\t0x38\t0xff\t0xff\t1\t1\t1\t0x801
+"
+        .parse()
+        .unwrap();
+
+        let key = |code: &str| KeyID::One(code.parse().unwrap());
+        assert_eq!(
+            patch.statements,
+            [
+                Statement {
+                    context: ContextKind::MainGameplay,
+                    op: PatchOp::SetBinding {
+                        event: "Shout".into(),
+                        device: Device::Gamepad,
+                        key_id: KeyID::Or(vec![
+                            KeyID::And(vec![key("0x100"), key("0x1000")]),
+                            key("0x9"),
+                        ]),
+                    },
+                },
+                Statement {
+                    context: ContextKind::Console,
+                    op: PatchOp::SetRemappable {
+                        event: "Auto-Move".into(),
+                        device: Device::Mouse,
+                        remappable: false,
+                    },
+                },
+                Statement {
+                    context: ContextKind::Other(20),
+                    op: PatchOp::RemoveFlag {
+                        event: "Auto-Move".into(),
+                        flag: UserEventFlag::MAIN_FOUR | UserEventFlag::MOVEMENT,
+                    },
+                },
+                Statement {
+                    context: ContextKind::Other(20),
+                    op: PatchOp::SetFlag {
+                        event: "Auto-Move".into(),
+                        flag: None,
+                    },
+                },
+                Statement {
+                    context: ContextKind::Other(20),
+                    op: PatchOp::AddEvent(EventLine {
+                        event_name: "Dodge".into(),
+                        keyboard_id: key("0x38"),
+                        mouse_id: key("0xff"),
+                        gamepad_id: key("0xff"),
+                        remap_key: true,
+                        remap_mouse: true,
+                        remap_gamepad: true,
+                        event_binary_flag: Some("0x801".into()),
+                    }),
+                },
+            ]
+        );
+
+        let err = "Jump.keyboard = NoSuchKey".parse::<Patch>().unwrap_err();
+        assert_eq!(
+            err,
+            ControlMapError::InvalidPatch {
+                line: 1,
+                message: "expected key names or hex codes".into()
+            }
+        );
+        assert!("[Nowhere] remove Jump".parse::<Patch>().is_err());
+    }
+
+    #[test]
+    fn test_generated_patch_round_trips() {
+        let old = ControlMap::from_txt(include_str!("../examples/controlmap.txt")).unwrap();
+        let mut new = old.clone();
+        new.remove_event(ContextKind::MenuMode, "Cancel").unwrap();
+        new.set_binding(
+            ContextKind::MainGameplay,
+            "Shout",
+            Device::Gamepad,
+            KeyID::Alias("Sprint".into()),
+        )
+        .unwrap();
+        let mut dodge = new.context(ContextKind::Favor).unwrap().events[0].clone();
+        dodge.event_name = "Dodge".into();
+        new.insert_event(ContextKind::Favor, dodge).unwrap();
+
+        let patch = Patch::between(&old, &new).unwrap();
+        assert_eq!(patch.statements.len(), 3);
+        let reparsed: Patch = patch.to_string().parse().unwrap();
+        assert_eq!(reparsed, patch);
+
+        let mut patched = old.clone();
+        assert_eq!(patched.apply_patch(&reparsed), []);
+        assert!(patched.diff(&new).is_empty());
+
+        let wide =
+            ControlMap::from_txt("Jump\t0x39\t0xff\t0x1000\t1\t1\t1\t0x100000000\n").unwrap();
+        assert!(Patch::between(&old, &wide).is_err());
+    }
+}
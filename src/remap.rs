@@ -0,0 +1,192 @@
+//! Rebinding as done by the in-game Controls menu.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{context::ContextKind, scan_code::Device, ControlMap};
+//!
+//! let mut control_map = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+//!
+//! // Give Jump the key of Activate (`E`); Activate gets Jump's old key.
+//! let displaced = control_map.remap(ContextKind::MainGameplay, "Jump", Device::Keyboard, 0x12)?;
+//! assert_eq!(displaced[0].event, "Activate");
+//! assert_eq!(displaced[0].current.to_string(), "0x19");
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::{ControlMap, ControlMapError, Result};
+use crate::parser::{EventLine, KeyCode, KeyID, Line};
+use crate::scan_code::{Device, UNMAPPED};
+
+/// An event whose binding changed because another event took its key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Displaced {
+    pub context: ContextKind,
+    pub event: String,
+    pub device: Device,
+    pub previous: KeyID,
+    pub current: KeyID,
+}
+
+impl ControlMap {
+    /// Bind the event named `name` in `context` to `code` of `device`, like the Controls menu.
+    ///
+    /// An event of the same context already bound to exactly `code` is given the old binding of
+    /// `name` in exchange and returned. Events using `code` only in a combination keep it.
+    /// `0xff` unbinds the event without touching any other.
+    ///
+    /// # Errors
+    /// - The context or event does not exist.
+    /// - `code` is not a key of `device`.
+    /// - The event may not be remapped on `device`.
+    /// - The event holding `code` may not be remapped, so the key cannot be taken from it.
+    pub fn remap(
+        &mut self,
+        context: ContextKind,
+        name: &str,
+        device: Device,
+        code: u32,
+    ) -> Result<Vec<Displaced>> {
+        if code != UNMAPPED && device.code_name(code).is_none() {
+            return Err(ControlMapError::UnknownKey { device, code });
+        }
+        let position = self.find_event(context, name)?;
+        let event = self.event_at(position);
+        if !event.remappable(device) {
+            return Err(ControlMapError::NotRemappable {
+                context,
+                event: name.into(),
+                device,
+            });
+        }
+        let previous = event.key_id(device).clone();
+        let key_id = KeyID::One(KeyCode::new(code));
+
+        let holder = self
+            .event_indices(context)?
+            .into_iter()
+            .filter(|&idx| idx != position && code != UNMAPPED)
+            .find(|&idx| self.event_at(idx).key_id(device) == &key_id);
+        let mut displaced = Vec::new();
+        if let Some(holder) = holder {
+            let event = self.event_at(holder);
+            if !event.remappable(device) {
+                return Err(ControlMapError::KeyReserved {
+                    context,
+                    event: event.event_name.clone(),
+                    device,
+                    key: KeyCode::new(code),
+                });
+            }
+            displaced.push(Displaced {
+                context,
+                event: event.event_name.clone(),
+                device,
+                previous: event.key_id(device).clone(),
+                current: previous.clone(),
+            });
+            self.set_binding(context, &displaced[0].event, device, previous)?;
+        }
+
+        self.set_binding(context, name, device, key_id)?;
+        Ok(displaced)
+    }
+
+    fn event_at(&self, position: usize) -> &EventLine {
+        match &self.lines[position] {
+            Line::EventLine(event) => event,
+            _ => unreachable!("event positions only point at event lines"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const INPUT: &str = "// Main Gameplay\nJump\t0x39\t0xff\t0x1000\t1\t1\t1\nActivate\t0x12\t0xff\t0x2000\t1\t1\t1\nConsole\t0x29\t0xff\t0x0020+0x0010\t0\t0\t0\n";
+
+    #[test]
+    fn test_remap_swaps() {
+        let mut control_map = ControlMap::from_txt(INPUT).unwrap();
+        let displaced = control_map
+            .remap(ContextKind::MainGameplay, "Jump", Device::Gamepad, 0x2000)
+            .unwrap();
+        assert_eq!(
+            displaced,
+            [Displaced {
+                context: ContextKind::MainGameplay,
+                event: "Activate".into(),
+                device: Device::Gamepad,
+                previous: KeyID::One("0x2000".parse().unwrap()),
+                current: KeyID::One("0x1000".parse().unwrap()),
+            }]
+        );
+        let main = control_map.context(ContextKind::MainGameplay).unwrap();
+        assert_eq!(main.events[0].gamepad_id.to_string(), "0x2000");
+        assert_eq!(main.events[1].gamepad_id.to_string(), "0x1000");
+    }
+
+    #[test]
+    fn test_remap_refuses_locked_events() {
+        let mut control_map = ControlMap::from_txt(INPUT).unwrap();
+        assert_eq!(
+            control_map.remap(ContextKind::MainGameplay, "Console", Device::Keyboard, 0x3b),
+            Err(ControlMapError::NotRemappable {
+                context: ContextKind::MainGameplay,
+                event: "Console".into(),
+                device: Device::Keyboard,
+            })
+        );
+        assert_eq!(
+            control_map.remap(ContextKind::MainGameplay, "Jump", Device::Keyboard, 0x29),
+            Err(ControlMapError::KeyReserved {
+                context: ContextKind::MainGameplay,
+                event: "Console".into(),
+                device: Device::Keyboard,
+                key: KeyCode::new(0x29),
+            })
+        );
+        assert_eq!(control_map, ControlMap::from_txt(INPUT).unwrap());
+    }
+
+    #[test]
+    fn test_remap_unbinds() {
+        let input = INPUT.replace("0x2000\t1", "0xff\t1");
+        let mut control_map = ControlMap::from_txt(&input).unwrap();
+        let displaced = control_map
+            .remap(ContextKind::MainGameplay, "Jump", Device::Gamepad, 0xff)
+            .unwrap();
+        assert!(displaced.is_empty());
+        let main = control_map.context(ContextKind::MainGameplay).unwrap();
+        assert_eq!(main.events[0].gamepad_id.to_string(), "0xff");
+        assert_eq!(main.events[1].gamepad_id.to_string(), "0xff");
+
+        // Locked events without a key, like `Move`, do not reserve 0xff.
+        let mut vanilla = ControlMap::from_txt(include_str!("../examples/controlmap.txt")).unwrap();
+        let displaced = vanilla
+            .remap(ContextKind::MainGameplay, "Jump", Device::Keyboard, 0xff)
+            .unwrap();
+        assert!(displaced.is_empty());
+    }
+
+    #[test]
+    fn test_remap_rejects_unknown_codes() {
+        let mut control_map = ControlMap::from_txt(INPUT).unwrap();
+        assert_eq!(
+            control_map.remap(
+                ContextKind::MainGameplay,
+                "Jump",
+                Device::Keyboard,
+                0xdead_beef
+            ),
+            Err(ControlMapError::UnknownKey {
+                device: Device::Keyboard,
+                code: 0xdead_beef,
+            })
+        );
+        assert_eq!(control_map, ControlMap::from_txt(INPUT).unwrap());
+    }
+}
@@ -0,0 +1,141 @@
+//! Reverse lookup from a physical key to the events it triggers.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{
+//!     context::ContextKind,
+//!     scan_code::{Device, KeyboardCode},
+//!     usage::KeyRole,
+//!     ControlMap,
+//! };
+//!
+//! let control_map = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+//! let usages = control_map.events_for_key(Device::Keyboard, KeyboardCode::Q as u32);
+//! let summary: Vec<_> = usages
+//!     .iter()
+//!     .map(|usage| (usage.context, usage.event.event_name.as_str(), &usage.role))
+//!     .collect();
+//! assert_eq!(
+//!     summary,
+//!     [
+//!         (ContextKind::MainGameplay, "Toggle POV", &KeyRole::Single),
+//!         // `!0,Toggle POV`
+//!         (ContextKind::ItemMenus, "YButton", &KeyRole::Single),
+//!         (ContextKind::FavoritesMenu, "Cancel", &KeyRole::Alternative),
+//!     ]
+//! );
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::ControlMap;
+use crate::parser::{EventLine, KeyCode, KeyID};
+use crate::scan_code::Device;
+
+/// How a key takes part in a binding.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyRole {
+    /// The key is the whole binding.
+    Single,
+    /// The key is one of several alternatives, e.g. `0x10` in `0x10,0x0f`.
+    Alternative,
+    /// The key must be held together with the others of the chord, e.g. `0x1000` in
+    /// `0x0200+0x1000`.
+    Chord(Vec<KeyCode>),
+}
+
+/// An event triggered by a key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyUsage<'a> {
+    pub context: ContextKind,
+    pub event: &'a EventLine,
+    pub role: KeyRole,
+    /// Aliases followed to reach the key; empty if the event binds it directly.
+    pub alias_chain: Vec<String>,
+}
+
+impl ControlMap {
+    /// Every event, in file order, that `code` of `device` triggers or helps trigger.
+    ///
+    /// Aliases are resolved; those that cannot be are skipped.
+    pub fn events_for_key(&self, device: Device, code: u32) -> Vec<KeyUsage<'_>> {
+        let mut usages = Vec::new();
+        for context in self.contexts() {
+            for event in context.events {
+                let Ok(resolved) = self.resolve_alias(event.key_id(device), device) else {
+                    continue;
+                };
+                let mut roles = Vec::new();
+                find_roles(&resolved.key_id, code, false, &mut roles);
+                roles.dedup();
+                usages.extend(roles.into_iter().map(|role| KeyUsage {
+                    context: context.kind,
+                    event,
+                    role,
+                    alias_chain: resolved.chain.clone(),
+                }));
+            }
+        }
+        usages
+    }
+}
+
+fn find_roles(key_id: &KeyID, code: u32, in_or: bool, roles: &mut Vec<KeyRole>) {
+    match key_id {
+        KeyID::Or(keys) => {
+            for key in keys {
+                find_roles(key, code, true, roles);
+            }
+        }
+        KeyID::And(_) => {
+            let mut chord = Vec::new();
+            collect_codes(key_id, &mut chord);
+            if chord.iter().any(|key| key.code() == code) {
+                roles.push(KeyRole::Chord(chord));
+            }
+        }
+        KeyID::One(key) if key.code() == code => roles.push(match in_or {
+            true => KeyRole::Alternative,
+            false => KeyRole::Single,
+        }),
+        KeyID::One(_) | KeyID::Alias(_) => {}
+    }
+}
+
+fn collect_codes(key_id: &KeyID, codes: &mut Vec<KeyCode>) {
+    match key_id {
+        KeyID::Or(keys) | KeyID::And(keys) => {
+            for key in keys {
+                collect_codes(key, codes);
+            }
+        }
+        KeyID::One(key) => codes.push(key.clone()),
+        KeyID::Alias(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_roles_and_aliases() {
+        let control_map = ControlMap::from_txt(
+            "// Main Gameplay\nShout\t0x2c\t0xff\t0x0200+0x1000\t1\t1\t1\n\n// Menu Mode\nAccept\t0x1c\t0xff\t!0,Shout\t0\t0\t0\n",
+        )
+        .unwrap();
+        let usages = control_map.events_for_key(Device::Gamepad, 0x1000);
+        let chord = KeyRole::Chord(vec!["0x0200".parse().unwrap(), "0x1000".parse().unwrap()]);
+
+        assert_eq!(usages.len(), 2);
+        assert_eq!(usages[0].role, chord);
+        assert_eq!(usages[1].context, ContextKind::MenuMode);
+        assert_eq!(usages[1].role, chord);
+        assert_eq!(usages[1].alias_chain, ["Shout"]);
+        assert!(control_map
+            .events_for_key(Device::Keyboard, 0x1000)
+            .is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+//! User event flag groups.
+//!
+//! The optional 8th column of an event line groups related user events so the game can turn
+//! them on and off together (e.g. `DisablePlayerControls` toggling "Movement" or "Menu").
+//!
+//! # References
+//! - [CommonLibSSE/UserEvents.h](https://github.com/Ryan-rsm-McKenzie/CommonLibSSE/blob/master/include/RE/U/UserEvents.h)
+//!   - search `USER_EVENT_FLAG`
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{parser::Line, user_event::UserEventFlag, ControlMap};
+//!
+//! let control_map = ControlMap::from_txt("Jump\t0x39\t0xff\t0x1000\t1\t1\t1\t0xc01\n")?;
+//! let Some(Line::EventLine(jump)) = control_map.iter().next() else {
+//!     unreachable!()
+//! };
+//!
+//! let flag = jump.user_event_flag()?.unwrap();
+//! assert_eq!(
+//!     flag,
+//!     UserEventFlag::MOVEMENT | UserEventFlag::JUMPING | UserEventFlag::VATS
+//! );
+//! assert_eq!(flag.to_string(), "0xc01");
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use crate::context::ContextKind;
+use crate::controlmap::ControlMap;
+use crate::parser::EventLine;
+use crate::scan_code::ScanCodeError;
+use core::{fmt, str::FromStr};
+
+bitflags::bitflags! {
+    /// Groups of the `event_binary_flag` column. Unknown bits are kept as they are.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct UserEventFlag: u32 {
+        const MOVEMENT = 1 << 0;
+        const LOOKING = 1 << 1;
+        const ACTIVATE = 1 << 2;
+        const MENU = 1 << 3;
+        const CONSOLE = 1 << 4;
+        /// First/third person view switch
+        const POV = 1 << 5;
+        const FIGHTING = 1 << 6;
+        const SNEAKING = 1 << 7;
+        /// The four main menus: Inventory, Magic, Map and Stats
+        const MAIN_FOUR = 1 << 8;
+        const WHEEL_ZOOM = 1 << 9;
+        const JUMPING = 1 << 10;
+        const VATS = 1 << 11;
+
+        const _ = !0;
+    }
+}
+
+impl fmt::Display for UserEventFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:x}", self.bits())
+    }
+}
+
+impl FromStr for UserEventFlag {
+    type Err = ScanCodeError;
+
+    /// Parse the hex form used by `controlmap.txt`, e.g. `0x801`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .ok_or_else(|| ScanCodeError::InvalidDigit(s.into()))?;
+        u32::from_str_radix(digits, 16)
+            .map(Self::from_bits_retain)
+            .map_err(|_| ScanCodeError::InvalidDigit(s.into()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for UserEventFlag {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for UserEventFlag {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl ControlMap {
+    /// Events carrying any of the groups in `flag`, i.e. the events turned off when the game
+    /// disables those groups. Events whose flag cannot be decoded are skipped.
+    ///
+    /// # Examples
+    /// ```
+    /// use controlmap_parser::{context::ContextKind, user_event::UserEventFlag, ControlMap};
+    ///
+    /// let control_map = ControlMap::from_txt(include_str!("../examples/controlmap.txt"))?;
+    /// let sneaking: Vec<_> = control_map
+    ///     .events_with_flag(UserEventFlag::SNEAKING)
+    ///     .into_iter()
+    ///     .map(|(context, event)| (context, event.event_name.as_str()))
+    ///     .collect();
+    /// assert_eq!(sneaking, [(ContextKind::MainGameplay, "Sneak")]);
+    /// # Ok::<(), controlmap_parser::ControlMapError>(())
+    /// ```
+    pub fn events_with_flag(&self, flag: UserEventFlag) -> Vec<(ContextKind, &EventLine)> {
+        self.contexts()
+            .into_iter()
+            .flat_map(|context| {
+                context
+                    .events
+                    .into_iter()
+                    .map(move |event| (context.kind, event))
+            })
+            .filter(|(_, event)| match event.user_event_flag() {
+                Ok(Some(event_flag)) => event_flag.intersects(flag),
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// List, for every flag bit, the events carrying it.
+    ///
+    /// Known groups are always listed; unknown bits only when some event uses them.
+    pub fn flag_report(&self) -> FlagReport {
+        let groups = (0..u32::BITS)
+            .map(|bit| UserEventFlag::from_bits_retain(1 << bit))
+            .filter_map(|flag| {
+                let events: Vec<_> = self
+                    .events_with_flag(flag)
+                    .into_iter()
+                    .map(|(context, event)| (context, event.event_name.clone()))
+                    .collect();
+                let known = UserEventFlag::all()
+                    .iter_names()
+                    .any(|(_, named)| named == flag);
+                (known || !events.is_empty()).then_some(FlagGroup { flag, events })
+            })
+            .collect();
+        FlagReport { groups }
+    }
+}
+
+/// Result of [`ControlMap::flag_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlagReport {
+    /// One entry per flag bit, lowest bit first.
+    pub groups: Vec<FlagGroup>,
+}
+
+/// Events sharing one flag bit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlagGroup {
+    /// A single bit.
+    pub flag: UserEventFlag,
+    /// Context and name of each event carrying the bit, in file order.
+    pub events: Vec<(ContextKind, String)>,
+}
+
+impl FlagGroup {
+    /// Name of the bit, e.g. `MOVEMENT`, if it is a known group.
+    pub fn name(&self) -> Option<&'static str> {
+        self.flag
+            .iter_names()
+            .next()
+            .map(|(name, _)| name)
+            .filter(|_| self.flag.bits().count_ones() == 1)
+    }
+}
+
+impl fmt::Display for FlagReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for group in &self.groups {
+            writeln!(f, "{} {}", group.flag, group.name().unwrap_or("UNKNOWN"))?;
+            let mut events = group.events.iter().peekable();
+            while let Some((context, _)) = events.peek().copied() {
+                let mut names = Vec::new();
+                while let Some((_, name)) = events.next_if(|(next, _)| next == context) {
+                    names.push(name.as_str());
+                }
+                writeln!(f, "    {}: {}", context, names.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_and_format() {
+        let flag: UserEventFlag = "0x108".parse().unwrap();
+        assert_eq!(flag, UserEventFlag::MENU | UserEventFlag::MAIN_FOUR);
+        assert_eq!(flag.to_string(), "0x108");
+        assert_eq!(
+            "0x0008".parse::<UserEventFlag>().unwrap().to_string(),
+            "0x8"
+        );
+        assert!("0xzz".parse::<UserEventFlag>().is_err());
+        assert!("801".parse::<UserEventFlag>().is_err());
+    }
+
+    #[test]
+    fn test_unknown_bits_are_kept() {
+        let flag: UserEventFlag = "0x80001001".parse().unwrap();
+        assert!(flag.contains(UserEventFlag::MOVEMENT));
+        assert_eq!(flag.to_string(), "0x80001001");
+        assert_eq!(
+            flag.iter_names().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["MOVEMENT"]
+        );
+    }
+
+    #[test]
+    fn test_flag_report() {
+        let control_map = ControlMap::from_txt(
+            "// Main Gameplay\nSneak\t0x1d\t0xff\t0x0080\t1\t1\t1\t0x481\n\n// Menu Mode\nCancel\t0x0f\t0xff\t0x2000\t0\t0\t0\t0x10008\n",
+        )
+        .unwrap();
+        let report = control_map.flag_report();
+
+        assert_eq!(report.groups.len(), 13);
+        assert_eq!(
+            report.groups[7],
+            FlagGroup {
+                flag: UserEventFlag::SNEAKING,
+                events: vec![(ContextKind::MainGameplay, "Sneak".into())],
+            }
+        );
+        assert_eq!(report.groups[12].name(), None);
+        assert!(report
+            .to_string()
+            .ends_with("0x10000 UNKNOWN\n    Menu Mode: Cancel\n"));
+    }
+}
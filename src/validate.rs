@@ -0,0 +1,338 @@
+//! Validation of the input context layout.
+//!
+//! The game identifies contexts only by position, so a missing or extra blank line silently
+//! shifts every later context. The header comments (`// Menu Mode`, ...) are not read by the
+//! game, but they tell us what the author meant each section to be, which is enough to say
+//! which context went missing or got duplicated.
+//!
+//! # Examples
+//!
+//! ```
+//! use controlmap_parser::{
+//!     context::ContextKind,
+//!     validate::{ContextIssue, GameProfile},
+//!     ControlMap,
+//! };
+//!
+//! let vanilla = include_str!("../examples/controlmap.txt");
+//! let control_map = ControlMap::from_txt(vanilla)?;
+//! assert!(control_map.validate_contexts(GameProfile::SkyrimSE).is_valid());
+//!
+//! // Forgot the blank line in front of `// Stats`.
+//! let broken = vanilla.replace("\r\n\r\n// Stats", "\r\n// Stats");
+//! let report = ControlMap::from_txt(&broken)?.validate_contexts(GameProfile::SkyrimSE);
+//! assert_eq!((report.expected, report.found), (17, 16));
+//! assert_eq!(
+//!     report.issues,
+//!     [ContextIssue::Missing {
+//!         expected: ContextKind::Stats,
+//!         index: 8,
+//!         line: 125,
+//!     }]
+//! );
+//! # Ok::<(), controlmap_parser::ControlMapError>(())
+//! ```
+use crate::context::{ContextKind, InputContext};
+use crate::controlmap::ControlMap;
+use core::fmt;
+
+/// Game whose context layout is expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameProfile {
+    /// Legendary Edition, which has no `Favor` context.
+    SkyrimLE,
+    /// Special Edition and Anniversary Edition
+    SkyrimSE,
+    SkyrimVR,
+}
+
+/// Contexts of Skyrim LE: the SE layout without `Favor`.
+const SKYRIM_LE: [ContextKind; 16] = [
+    ContextKind::MainGameplay,
+    ContextKind::MenuMode,
+    ContextKind::Console,
+    ContextKind::ItemMenus,
+    ContextKind::Inventory,
+    ContextKind::DebugText,
+    ContextKind::FavoritesMenu,
+    ContextKind::MapMenu,
+    ContextKind::Stats,
+    ContextKind::Cursor,
+    ContextKind::Book,
+    ContextKind::DebugOverlay,
+    ContextKind::Journal,
+    ContextKind::TFCMode,
+    ContextKind::DebugMapMenu,
+    ContextKind::Lockpicking,
+];
+
+/// Contexts of Skyrim VR, which shares the SE layout.
+const SKYRIM_VR: [ContextKind; 17] = ContextKind::ALL;
+
+impl GameProfile {
+    /// Contexts the game reads, in order.
+    pub fn contexts(&self) -> &'static [ContextKind] {
+        match self {
+            Self::SkyrimLE => &SKYRIM_LE,
+            Self::SkyrimSE => &ContextKind::ALL,
+            Self::SkyrimVR => &SKYRIM_VR,
+        }
+    }
+}
+
+/// Result of [`ControlMap::validate_contexts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContextReport {
+    /// Number of contexts the game expects.
+    pub expected: usize,
+    /// Number of blank line separated sections in the file.
+    pub found: usize,
+    pub issues: Vec<ContextIssue>,
+}
+
+impl ContextReport {
+    pub fn is_valid(&self) -> bool {
+        self.expected == self.found && self.issues.is_empty()
+    }
+}
+
+/// A problem with the context layout.
+///
+/// `index` is the 0-based position of a section in the file and `line` the 1-based line
+/// on which that section starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ContextIssue {
+    /// No section seems to be `expected`; the section at `index` is the next one after the gap.
+    Missing {
+        expected: ContextKind,
+        index: usize,
+        line: usize,
+    },
+    /// The section at `index` looks like a second copy of `kind`.
+    Duplicated {
+        kind: ContextKind,
+        index: usize,
+        line: usize,
+    },
+    /// The section at `index` does not correspond to any expected context.
+    Unexpected {
+        header: Option<String>,
+        index: usize,
+        line: usize,
+    },
+    /// The game reads the section at `index` as `expected`, but its header says `found`.
+    OutOfOrder {
+        expected: ContextKind,
+        found: ContextKind,
+        index: usize,
+        line: usize,
+    },
+}
+
+impl fmt::Display for ContextIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { expected, line, .. } => {
+                write!(
+                    f,
+                    "line {line}: context `{expected}` is missing before this line"
+                )
+            }
+            Self::Duplicated { kind, line, .. } => {
+                write!(f, "line {line}: context `{kind}` appears again")
+            }
+            Self::Unexpected { header, line, .. } => match header {
+                Some(header) => write!(f, "line {line}: unexpected context `{header}`"),
+                None => write!(f, "line {line}: unexpected extra context"),
+            },
+            Self::OutOfOrder {
+                expected,
+                found,
+                line,
+                ..
+            } => write!(
+                f,
+                "line {line}: context `{found}` is read by the game as `{expected}`"
+            ),
+        }
+    }
+}
+
+impl ControlMap {
+    /// Check the number and order of contexts against `profile`, using header comments as hints.
+    pub fn validate_contexts(&self, profile: GameProfile) -> ContextReport {
+        let expected = profile.contexts();
+        let sections = self.contexts();
+        let hints: Vec<_> = sections
+            .iter()
+            .map(|section| section.header.and_then(ContextKind::from_header))
+            .collect();
+        let line_of = |index: usize| {
+            sections
+                .get(index)
+                .map_or(self.iter().len() + 1, |section| section.lines.start + 1)
+        };
+
+        // Edit distance between the hinted sections and the expected layout.
+        // A section without a recognizable header may stand for any context.
+        let (m, n) = (sections.len(), expected.len());
+        let substitution = |i: usize, j: usize| match hints[i] {
+            Some(hint) if hint != expected[j] => 1,
+            _ => 0,
+        };
+        let mut cost = vec![vec![0_usize; n + 1]; m + 1];
+        for i in (0..=m).rev() {
+            for j in (0..=n).rev() {
+                cost[i][j] = match (i < m, j < n) {
+                    (true, true) => (substitution(i, j) + cost[i + 1][j + 1])
+                        .min(1 + cost[i + 1][j])
+                        .min(1 + cost[i][j + 1]),
+                    (true, false) => 1 + cost[i + 1][j],
+                    (false, true) => 1 + cost[i][j + 1],
+                    (false, false) => 0,
+                };
+            }
+        }
+
+        let mut issues = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < m || j < n {
+            if i < m && j < n && cost[i][j] == substitution(i, j) + cost[i + 1][j + 1] {
+                if let Some(found) = hints[i].filter(|&hint| hint != expected[j]) {
+                    issues.push(ContextIssue::OutOfOrder {
+                        expected: expected[j],
+                        found,
+                        index: i,
+                        line: line_of(i),
+                    });
+                }
+                i += 1;
+                j += 1;
+            } else if i < m && cost[i][j] == 1 + cost[i + 1][j] {
+                issues.push(extra_section(&sections, &hints, i, line_of(i)));
+                i += 1;
+            } else {
+                issues.push(ContextIssue::Missing {
+                    expected: expected[j],
+                    index: i,
+                    line: line_of(i),
+                });
+                j += 1;
+            }
+        }
+
+        ContextReport {
+            expected: n,
+            found: m,
+            issues,
+        }
+    }
+}
+
+fn extra_section(
+    sections: &[InputContext<'_>],
+    hints: &[Option<ContextKind>],
+    index: usize,
+    line: usize,
+) -> ContextIssue {
+    match hints[index] {
+        Some(kind) if hints[..index].contains(&Some(kind)) => {
+            ContextIssue::Duplicated { kind, index, line }
+        }
+        _ => ContextIssue::Unexpected {
+            header: sections[index].header.map(Into::into),
+            index,
+            line,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const VANILLA: &str = include_str!("../examples/controlmap.txt");
+
+    fn validate(input: &str) -> ContextReport {
+        ControlMap::from_txt(input)
+            .unwrap()
+            .validate_contexts(GameProfile::SkyrimSE)
+    }
+
+    #[test]
+    fn test_profiles() {
+        let control_map = ControlMap::from_txt(VANILLA).unwrap();
+        assert!(control_map
+            .validate_contexts(GameProfile::SkyrimVR)
+            .is_valid());
+
+        let report = control_map.validate_contexts(GameProfile::SkyrimLE);
+        assert_eq!((report.expected, report.found), (16, 17));
+        assert_eq!(
+            report.issues,
+            [ContextIssue::Unexpected {
+                header: Some("Favor".into()),
+                index: 16,
+                line: 191,
+            }]
+        );
+
+        let favor = "\r\n\r\n// Favor\r\n";
+        let le = &VANILLA[..VANILLA.find(favor).unwrap()];
+        let report = ControlMap::from_txt(le)
+            .unwrap()
+            .validate_contexts(GameProfile::SkyrimLE);
+        assert!(report.is_valid(), "{report:?}");
+    }
+
+    #[test]
+    fn test_missing_section() {
+        let input = VANILLA.replace(
+            "// Inventory\r\nChargeItem\t0x14\t0xff\t0x0200\t0\t0\t0\r\n\r\n",
+            "",
+        );
+        let report = validate(&input);
+        assert_eq!(report.found, 16);
+        assert_eq!(
+            report.issues,
+            [ContextIssue::Missing {
+                expected: ContextKind::Inventory,
+                index: 4,
+                line: 89,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_duplicated_section() {
+        let stats = "// Stats\r\nRotate\t0xff\t0xff\t0x000b\t0\t0\t0\t0x8\r\n\r\n";
+        let input = VANILLA.replace(stats, &stats.repeat(2));
+        let report = validate(&input);
+        assert_eq!(report.found, 18);
+        assert_eq!(
+            report.issues,
+            [ContextIssue::Duplicated {
+                kind: ContextKind::Stats,
+                index: 9,
+                line: 126,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_trailing_blank_line() {
+        let report = validate(&format!("{VANILLA}\r\n\r\n"));
+        assert_eq!(
+            report.issues,
+            [ContextIssue::Unexpected {
+                header: None,
+                index: 17,
+                line: 194,
+            }]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! `from_txt(x.to_string()) == x` for every value the parser can produce.
+#![cfg(feature = "proptest")]
+use controlmap_parser::{
+    parser::{EventLine, KeyID, Line},
+    ControlMap,
+};
+use proptest::prelude::*;
+
+fn reparse(txt: &str) -> ControlMap {
+    ControlMap::from_txt(txt).unwrap_or_else(|err| panic!("{err}\n{txt:?}"))
+}
+
+proptest! {
+    #[test]
+    fn key_id_round_trip(key_id in any::<KeyID>()) {
+        let mut event: EventLine = reparse("Event\t0xff\t0xff\t0xff\t0\t0\t0")
+            .into_iter()
+            .find_map(|line| match line {
+                Line::EventLine(event) => Some(event),
+                _ => None,
+            })
+            .unwrap();
+        event.keyboard_id = key_id.clone();
+        event.gamepad_id = key_id;
+
+        let txt = Line::EventLine(event.clone()).to_string();
+        let parsed: Vec<Line> = reparse(&txt).into_iter().collect();
+        prop_assert_eq!(parsed, vec![Line::EventLine(event)]);
+    }
+
+    #[test]
+    fn line_round_trip(line in any::<Line>()) {
+        let txt = line.to_string();
+        let parsed = reparse(&txt);
+        prop_assert_eq!(parsed.to_string(), txt);
+        prop_assert_eq!(parsed.into_iter().collect::<Vec<_>>(), vec![line]);
+    }
+
+    #[test]
+    fn control_map_round_trip(control_map in any::<ControlMap>()) {
+        let txt = control_map.to_string();
+        let parsed = reparse(&txt);
+        prop_assert_eq!(parsed.to_string(), txt);
+        prop_assert_eq!(parsed, control_map);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip(control_map in any::<ControlMap>()) {
+        let json = serde_json::to_string(&control_map).unwrap();
+        let parsed: ControlMap = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(parsed.to_string(), control_map.to_string());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn human_json_round_trip(control_map in any::<ControlMap>()) {
+        let json = control_map.to_human_json().unwrap();
+        let parsed = ControlMap::from_human_json(&json).unwrap();
+        prop_assert_eq!(parsed.to_string(), control_map.to_string());
+        prop_assert_eq!(parsed, control_map);
+    }
+}